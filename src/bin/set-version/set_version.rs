@@ -1,7 +1,10 @@
 use std::path::Path;
 use std::path::PathBuf;
 
-use cargo_edit::{LocalManifest, shell_status, shell_warn, upgrade_requirement};
+use cargo_edit::{
+    EditJournal, LocalManifest, ManifestTransaction, is_script, root_manifest_path, shell_status,
+    shell_warn, undo_last_run, upgrade_requirement,
+};
 use clap::Args;
 
 use crate::errors::CargoResult;
@@ -56,6 +59,17 @@ pub(crate) struct VersionArgs {
     #[arg(long, short = 'n')]
     dry_run: bool,
 
+    /// Print a unified diff of the changes to each manifest
+    #[arg(long)]
+    diff: bool,
+
+    /// Restore the manifests and lockfile changed by the last run
+    #[arg(
+        long,
+        conflicts_with_all = ["target", "bump", "metadata", "pkgid", "all", "workspace", "dry_run", "exclude"]
+    )]
+    undo: bool,
+
     /// Crates to exclude and not modify.
     #[arg(long)]
     exclude: Vec<String>,
@@ -100,6 +114,8 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
         pkgid,
         all,
         dry_run,
+        diff,
+        undo,
         workspace,
         exclude,
         locked,
@@ -108,6 +124,10 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
         unstable_features: _,
     } = args;
 
+    if undo {
+        return undo_last_run(manifest_path.as_deref());
+    }
+
     let target = match (target, bump) {
         (None, None) => TargetVersion::Relative(BumpLevel::Release),
         (None, Some(level)) => TargetVersion::Relative(level),
@@ -116,7 +136,7 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
     };
 
     let ws_metadata = resolve_ws(manifest_path.as_deref(), locked, offline)?;
-    let root_manifest_path = ws_metadata.workspace_root.as_std_path().join("Cargo.toml");
+    let root_manifest_path = root_manifest_path(&ws_metadata, manifest_path.as_deref());
     let workspace_members = find_ws_members(&ws_metadata);
 
     let mut missing = Vec::new();
@@ -153,7 +173,6 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
     };
 
     let update_workspace_version;
-    let mut changed = false;
     if workspace && exclude.is_empty() {
         // Fast path
         update_workspace_version = true;
@@ -194,8 +213,9 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
         }
     }
 
+    let mut manifests = ManifestTransaction::new();
     if update_workspace_version {
-        let mut ws_manifest = LocalManifest::try_new(&root_manifest_path)?;
+        let ws_manifest = manifests.manifest_mut(&root_manifest_path)?;
         if let Some(current) = ws_manifest.get_workspace_version()
             && let Some(next) = target.bump(&current, metadata.as_deref())?
         {
@@ -204,10 +224,6 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
                 &format!("workspace version from {current} to {next}"),
             )?;
             ws_manifest.set_workspace_version(&next);
-            changed = true;
-            if !dry_run {
-                ws_manifest.write()?;
-            }
 
             // Deferring `update_dependents` to the per-package logic
         }
@@ -217,7 +233,7 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
         let current = &package.version;
         let next = target.bump(current, metadata.as_deref())?;
         if let Some(next) = next {
-            let mut manifest = LocalManifest::try_new(Path::new(&package.manifest_path))?;
+            let manifest = manifests.manifest_mut(Path::new(&package.manifest_path))?;
             if manifest.version_is_inherited() {
                 shell_status(
                     "Upgrading",
@@ -232,10 +248,6 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
                     &format!("{} from {} to {}", package.name, current, next),
                 )?;
                 manifest.set_package_version(&next);
-                changed = true;
-                if !dry_run {
-                    manifest.write()?;
-                }
             }
 
             let crate_root =
@@ -245,13 +257,34 @@ fn exec(args: VersionArgs) -> CargoResult<()> {
                 &next,
                 &root_manifest_path,
                 &workspace_members,
-                dry_run,
+                &mut manifests,
             )?;
         }
     }
 
-    if changed {
-        resolve_ws(manifest_path.as_deref(), locked, offline)?;
+    if diff {
+        for manifest in manifests.modified() {
+            manifest.write_diff()?;
+        }
+    }
+    if manifests.is_modified() {
+        if dry_run {
+            resolve_ws(manifest_path.as_deref(), locked, offline)?;
+        } else {
+            let mut journal = EditJournal::new(
+                ws_metadata.target_directory.as_std_path(),
+                "cargo set-version",
+            );
+            for manifest in manifests.modified() {
+                journal.record(&manifest.path)?;
+            }
+            journal.record(&ws_metadata.workspace_root.as_std_path().join("Cargo.lock"))?;
+            manifests.commit(|| {
+                resolve_ws(manifest_path.as_deref(), locked, offline)?;
+                Ok(())
+            })?;
+            journal.save()?;
+        }
     }
     if dry_run {
         shell_warn("aborting set-version due to dry run")?;
@@ -265,7 +298,7 @@ fn update_dependents(
     next: &semver::Version,
     root_manifest_path: &Path,
     workspace_members: &[cargo_metadata::Package],
-    dry_run: bool,
+    manifests: &mut ManifestTransaction,
 ) -> CargoResult<()> {
     // This is redundant with iterating over `workspace_members`
     // - As `get_dependency_tables_mut` returns workspace dependencies
@@ -275,7 +308,7 @@ fn update_dependents(
     // - Virtual manifests
     // - Nicer message to the user
     {
-        update_dependent(crate_root, next, root_manifest_path, "workspace", dry_run)?;
+        update_dependent(crate_root, next, root_manifest_path, "workspace", manifests)?;
     }
 
     for member in workspace_members.iter() {
@@ -284,7 +317,7 @@ fn update_dependents(
             next,
             member.manifest_path.as_std_path(),
             &member.name,
-            dry_run,
+            manifests,
         )?;
     }
 
@@ -310,10 +343,9 @@ fn update_dependent(
     next: &semver::Version,
     manifest_path: &Path,
     name: &str,
-    dry_run: bool,
+    manifests: &mut ManifestTransaction,
 ) -> CargoResult<()> {
-    let mut dep_manifest = LocalManifest::try_new(manifest_path)?;
-    let mut changed = false;
+    let dep_manifest = manifests.manifest_mut(manifest_path)?;
     let dep_crate_root = dep_manifest
         .path
         .parent()
@@ -336,12 +368,8 @@ fn update_dependent(
                 &format!("{name}'s dependency from {old_req} to {new_req}"),
             )?;
             dep.insert("version", toml_edit::value(new_req));
-            changed = true;
         }
     }
-    if changed && !dry_run {
-        dep_manifest.write()?;
-    }
 
     Ok(())
 }
@@ -363,6 +391,9 @@ fn resolve_ws(
     if offline {
         other.push("--offline".to_owned());
     }
+    if manifest_path.is_some_and(is_script) {
+        other.push("-Zscript".to_owned());
+    }
     cmd.other_options(other);
 
     let ws = cmd.exec()?;
@@ -0,0 +1,326 @@
+//! Security advisories from a local checkout of `rustsec/advisory-db`
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use cargo_edit::{CargoResult, PrereleasePolicy, RustVersion, find_minimal_version};
+use semver::{Version, VersionReq};
+
+/// Advisories read on demand from `<root>/crates/<name>/*.md`
+pub(crate) struct AdvisoryDb {
+    root: PathBuf,
+    crates: HashMap<String, Vec<Advisory>>,
+}
+
+impl AdvisoryDb {
+    pub(crate) fn open(root: &Path) -> CargoResult<Self> {
+        anyhow::ensure!(
+            root.join("crates").is_dir(),
+            "{} is not an advisory database, `crates` directory is missing",
+            root.display()
+        );
+        Ok(Self {
+            root: root.to_owned(),
+            crates: HashMap::new(),
+        })
+    }
+
+    /// Advisories in effect for the crate `name`
+    ///
+    /// Withdrawn advisories and those that are only informational, like unmaintained crates,
+    /// are skipped as no upgrade fixes them.
+    pub(crate) fn advisories(&mut self, name: &str) -> CargoResult<&[Advisory]> {
+        if !self.crates.contains_key(name) {
+            let advisories = load(&self.root.join("crates").join(name))?;
+            self.crates.insert(name.to_owned(), advisories);
+        }
+        Ok(&self.crates[name])
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Advisory {
+    pub(crate) id: String,
+    pub(crate) title: Option<String>,
+    patched: Vec<VersionReq>,
+    unaffected: Vec<VersionReq>,
+}
+
+impl Advisory {
+    pub(crate) fn affects(&self, version: &Version) -> bool {
+        !self
+            .patched
+            .iter()
+            .chain(&self.unaffected)
+            .any(|req| req.matches(version))
+    }
+}
+
+impl std::fmt::Display for Advisory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.title {
+            Some(title) => write!(f, "{} ({title})", self.id),
+            None => self.id.fmt(f),
+        }
+    }
+}
+
+/// A dependency whose locked version or requirement is affected by advisories
+pub(crate) struct Vulnerability {
+    /// Advisories affecting the dependency
+    pub(crate) advisories: Vec<Advisory>,
+    /// Affected versions, like `1.2.0 (locked)`
+    pub(crate) affected: Vec<String>,
+    /// The oldest version no advisory for the crate affects
+    pub(crate) fix: Option<Version>,
+    all: Vec<Advisory>,
+}
+
+impl Vulnerability {
+    /// Check the versions locked for a dependency on `old_req`, and its minimum
+    ///
+    /// `versions` are the crate's versions in the index, filtered like for any upgrade.
+    pub(crate) fn check(
+        advisories: &[Advisory],
+        old_req: &str,
+        locked: &[Version],
+        versions: &[tame_index::IndexVersion],
+        prerelease: PrereleasePolicy,
+        rust_version: Option<RustVersion>,
+    ) -> Option<Self> {
+        let req = VersionReq::parse(old_req).ok()?;
+        let mut candidates = locked
+            .iter()
+            .filter(|v| req.matches(v))
+            .map(|v| (v.clone(), format!("{v} (locked)")))
+            .collect::<Vec<_>>();
+        if let Some(minimum) = req_minimum(&req) {
+            candidates.push((
+                minimum.clone(),
+                format!("{minimum} (minimum of `{old_req}`)"),
+            ));
+        }
+
+        let mut affected = Vec::new();
+        let mut floor = None;
+        let mut affecting = Vec::new();
+        for (version, label) in candidates {
+            let matched = advisories
+                .iter()
+                .filter(|a| a.affects(&version))
+                .collect::<Vec<_>>();
+            if matched.is_empty() {
+                continue;
+            }
+            for advisory in matched {
+                if !affecting.iter().any(|a: &Advisory| a.id == advisory.id) {
+                    affecting.push(advisory.clone());
+                }
+            }
+            affected.push(label);
+            floor = floor.max(Some(version));
+        }
+        let floor = floor?;
+
+        let fix = find_minimal_version(
+            versions,
+            |v| floor <= *v && prerelease.allows(&floor.pre, v) && !is_affected(advisories, v),
+            rust_version,
+        )
+        .and_then(|d| d.version().and_then(|v| v.parse().ok()));
+        Some(Self {
+            advisories: affecting,
+            affected,
+            fix,
+            all: advisories.to_vec(),
+        })
+    }
+
+    /// Whether a dependency on `req` could still resolve to an affected version
+    pub(crate) fn affects_req(&self, req: &str) -> bool {
+        VersionReq::parse(req)
+            .ok()
+            .and_then(|req| req_minimum(&req))
+            .is_none_or(|minimum| is_affected(&self.all, &minimum))
+    }
+
+    /// The requirement reaching [`Vulnerability::fix`], keeping the style of `old_req` when
+    /// possible
+    pub(crate) fn fix_req(&self, old_req: &str) -> Option<String> {
+        let fix = self.fix.as_ref()?;
+        let req = match cargo_edit::upgrade_requirement(old_req, fix) {
+            Ok(Some(req)) if !self.affects_req(&req) => req,
+            _ => fix.to_string(),
+        };
+        Some(req)
+    }
+}
+
+fn is_affected(advisories: &[Advisory], version: &Version) -> bool {
+    advisories.iter().any(|a| a.affects(version))
+}
+
+/// The version a requirement resolves to at the least, like `1.2.0` for `^1.2`
+pub(crate) fn req_minimum(req: &VersionReq) -> Option<Version> {
+    req.comparators
+        .iter()
+        .filter(|c| {
+            matches!(
+                c.op,
+                semver::Op::Exact
+                    | semver::Op::GreaterEq
+                    | semver::Op::Tilde
+                    | semver::Op::Caret
+                    | semver::Op::Wildcard
+            )
+        })
+        .map(|c| Version {
+            major: c.major,
+            minor: c.minor.unwrap_or(0),
+            patch: c.patch.unwrap_or(0),
+            pre: c.pre.clone(),
+            build: Default::default(),
+        })
+        .max()
+}
+
+fn load(dir: &Path) -> CargoResult<Vec<Advisory>> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+    let mut paths = entries
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to read {}", dir.display()))?;
+    paths.sort();
+
+    let mut advisories = Vec::new();
+    for path in paths {
+        if path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let advisory = parse(&content)
+            .with_context(|| format!("failed to parse advisory {}", path.display()))?;
+        advisories.extend(advisory);
+    }
+    Ok(advisories)
+}
+
+#[derive(serde_derive::Deserialize)]
+struct AdvisoryFile {
+    advisory: Metadata,
+    #[serde(default)]
+    versions: Versions,
+}
+
+#[derive(serde_derive::Deserialize)]
+struct Metadata {
+    id: String,
+    #[serde(default)]
+    informational: Option<String>,
+    #[serde(default)]
+    withdrawn: Option<toml::Value>,
+}
+
+#[derive(Default, serde_derive::Deserialize)]
+struct Versions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+/// Parse an advisory: a TOML front matter in a ```` ```toml ```` block, followed by a markdown
+/// description starting with its title
+fn parse(content: &str) -> CargoResult<Option<Advisory>> {
+    let (_, rest) = content
+        .split_once("```toml")
+        .context("missing ```toml block")?;
+    let (toml, description) = rest.split_once("```").context("unclosed ```toml block")?;
+    let file = toml::from_str::<AdvisoryFile>(toml)?;
+
+    let informational = file
+        .advisory
+        .informational
+        .as_deref()
+        .is_some_and(|kind| kind != "unsound");
+    if file.advisory.withdrawn.is_some() || informational {
+        return Ok(None);
+    }
+
+    let parse_reqs = |reqs: &[String]| {
+        reqs.iter()
+            .map(|req| {
+                VersionReq::parse(req).with_context(|| format!("invalid version range `{req}`"))
+            })
+            .collect::<CargoResult<Vec<_>>>()
+    };
+    let title = description
+        .lines()
+        .find_map(|l| l.strip_prefix("# "))
+        .map(|t| t.trim().to_owned());
+    Ok(Some(Advisory {
+        id: file.advisory.id,
+        title,
+        patched: parse_reqs(&file.versions.patched)?,
+        unaffected: parse_reqs(&file.versions.unaffected)?,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2021-0001"
+package = "foo"
+date = "2021-01-01"
+
+[versions]
+patched = [">= 1.2.3, < 2.0.0", ">= 2.0.1"]
+unaffected = ["< 1.0.0"]
+```
+
+# Use after free in `Foo::bar`
+
+Details.
+"#;
+
+    #[test]
+    fn parse_advisory() {
+        let advisory = parse(ADVISORY).unwrap().unwrap();
+        assert_eq!(advisory.id, "RUSTSEC-2021-0001");
+        assert_eq!(
+            advisory.title.as_deref(),
+            Some("Use after free in `Foo::bar`")
+        );
+        assert!(!advisory.affects(&Version::new(0, 9, 0)));
+        assert!(advisory.affects(&Version::new(1, 2, 0)));
+        assert!(!advisory.affects(&Version::new(1, 2, 3)));
+        assert!(advisory.affects(&Version::new(2, 0, 0)));
+        assert!(!advisory.affects(&Version::new(2, 1, 0)));
+    }
+
+    #[test]
+    fn skip_withdrawn_and_informational() {
+        let withdrawn = ADVISORY.replace("date = ", "withdrawn = \"2021-02-01\"\ndate = ");
+        assert!(parse(&withdrawn).unwrap().is_none());
+        let unmaintained = ADVISORY.replace("date = ", "informational = \"unmaintained\"\ndate = ");
+        assert!(parse(&unmaintained).unwrap().is_none());
+        let unsound = ADVISORY.replace("date = ", "informational = \"unsound\"\ndate = ");
+        assert!(parse(&unsound).unwrap().is_some());
+    }
+
+    #[test]
+    fn minimum_of_requirements() {
+        let min = |req: &str| req_minimum(&VersionReq::parse(req).unwrap());
+        assert_eq!(min("1.2"), Some(Version::new(1, 2, 0)));
+        assert_eq!(min(">=1.1, <2"), Some(Version::new(1, 1, 0)));
+        assert_eq!(min("1.*"), Some(Version::new(1, 0, 0)));
+        assert_eq!(min("<2"), None);
+    }
+}
@@ -0,0 +1,136 @@
+//! `# cargo-upgrade: ...` comments holding back the dependency they sit next to
+
+use anyhow::Context as _;
+use cargo_edit::CargoResult;
+use semver::{Op, VersionReq};
+
+const PREFIX: &str = "cargo-upgrade:";
+
+/// A comment like `# cargo-upgrade: max <1.30, reason="MSRV of downstream"`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Annotation {
+    pub(crate) directive: Directive,
+    pub(crate) reason: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Directive {
+    /// Keep the version requirement as is
+    Hold,
+    /// Only upgrade to versions matching an upper bound
+    Max(VersionReq),
+}
+
+impl Annotation {
+    /// The annotation in the comments around a dependency entry, on the lines above it or after
+    /// it on the same line
+    pub(crate) fn find(
+        key_decor: &toml_edit::Decor,
+        item: &toml_edit::Item,
+    ) -> CargoResult<Option<Self>> {
+        let item_decor = match item {
+            toml_edit::Item::Value(value) => Some(value.decor()),
+            toml_edit::Item::Table(table) => Some(table.decor()),
+            _ => None,
+        };
+        let comments = [Some(key_decor), item_decor]
+            .into_iter()
+            .flatten()
+            .flat_map(|decor| [decor.prefix(), decor.suffix()])
+            .flatten()
+            .filter_map(|raw| raw.as_str());
+        for comment in comments {
+            for line in comment.lines() {
+                let Some(text) = line.trim().strip_prefix('#') else {
+                    continue;
+                };
+                if let Some(text) = text.trim().strip_prefix(PREFIX) {
+                    return Self::parse(text.trim()).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn parse(text: &str) -> CargoResult<Self> {
+        let (directive, reason) = match text.split_once("reason=") {
+            Some((directive, reason)) => {
+                let reason = reason.trim();
+                let reason = reason
+                    .strip_prefix('"')
+                    .and_then(|r| r.strip_suffix('"'))
+                    .unwrap_or(reason);
+                let directive = directive.trim_end().trim_end_matches(',').trim_end();
+                (directive, Some(reason.to_owned()))
+            }
+            None => (text, None),
+        };
+        let directive = if directive == "hold" {
+            Directive::Hold
+        } else if let Some(req) = directive.strip_prefix("max ") {
+            let req = req.trim();
+            let req = VersionReq::parse(req)
+                .with_context(|| format!("invalid version requirement `{req}`"))?;
+            if !req
+                .comparators
+                .iter()
+                .all(|c| matches!(c.op, Op::Less | Op::LessEq))
+            {
+                anyhow::bail!("`max {req}` is not an upper bound like `<1.30`");
+            }
+            Directive::Max(req)
+        } else {
+            anyhow::bail!("unknown annotation `{directive}`, expected `hold` or `max <version>`");
+        };
+        Ok(Self { directive, reason })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn annotation(manifest: &str) -> CargoResult<Option<Annotation>> {
+        let mut doc = manifest.parse::<toml_edit::DocumentMut>().unwrap();
+        let table = doc["dependencies"].as_table_like_mut().unwrap();
+        let (key, item) = table.iter_mut().next().unwrap();
+        Annotation::find(key.leaf_decor(), item)
+    }
+
+    #[test]
+    fn hold_after_entry() {
+        let found = annotation("[dependencies]\ntokio = \"1.28\" # cargo-upgrade: hold\n").unwrap();
+        assert_eq!(
+            found,
+            Some(Annotation {
+                directive: Directive::Hold,
+                reason: None,
+            })
+        );
+    }
+
+    #[test]
+    fn max_above_entry() {
+        let found = annotation(
+            "[dependencies]\n# cargo-upgrade: max <1.30, reason=\"MSRV of downstream\"\ntokio = { version = \"1.28\" }\n",
+        )
+        .unwrap();
+        assert_eq!(
+            found,
+            Some(Annotation {
+                directive: Directive::Max("<1.30".parse().unwrap()),
+                reason: Some("MSRV of downstream".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn reject_other_annotations() {
+        assert!(annotation("[dependencies]\ntokio = \"1\" # cargo-upgrade: max >=1.30\n").is_err());
+        assert!(annotation("[dependencies]\ntokio = \"1\" # cargo-upgrade: skip\n").is_err());
+        assert_eq!(
+            annotation("[dependencies]\ntokio = \"1\" # hold\n").unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Hold back upgrades that would leave direct dependencies requiring different versions of
+//! each other
+
+use std::collections::BTreeMap;
+
+use semver::Version;
+
+use crate::dedupe::Release;
+
+/// A direct dependency, as seen by the planner
+pub(crate) struct Candidate {
+    /// The version in use now: locked, or the minimum of the requirement
+    pub(crate) current: Version,
+    /// Releases it may move to, newest first
+    pub(crate) releases: Vec<Release>,
+    /// The newest release, when only some requirements could move to its line, and what keeps
+    /// the others on the current one
+    pub(crate) stuck: Option<(Version, Vec<String>)>,
+}
+
+/// An upgrade held back below the newest release
+pub(crate) struct Hold {
+    pub(crate) version: Version,
+    pub(crate) latest: Version,
+    /// Why the newer releases were passed over, like "axum-extra 0.9.6 requires axum `^0.7`"
+    pub(crate) blockers: Vec<String>,
+}
+
+/// The newest releases of `candidates` whose requirements on each other hold, by name
+///
+/// When a release requires another candidate in a version it won't be at, an older release of
+/// the requiring crate that accepts it is preferred, then an older release of the required one.
+/// Candidates never go below their current version, and are held on their current line when
+/// only some of their requirements could leave it.
+pub(crate) fn plan(candidates: &BTreeMap<String, Candidate>) -> BTreeMap<String, Hold> {
+    let mut chosen = candidates
+        .keys()
+        .map(|name| (name.as_str(), 0))
+        .collect::<BTreeMap<_, _>>();
+    let version = |chosen: &BTreeMap<&str, usize>, name: &str| {
+        let candidate = &candidates[name];
+        candidate
+            .releases
+            .get(chosen[name])
+            .map(|r| r.version.clone())
+            .unwrap_or_else(|| candidate.current.clone())
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (name, candidate) in candidates {
+            let Some(release) = candidate.releases.get(chosen[name.as_str()]) else {
+                continue;
+            };
+            for (dep_name, req) in &release.deps {
+                if dep_name == name || !candidates.contains_key(dep_name) {
+                    continue;
+                }
+                let dep_version = version(&chosen, dep_name);
+                if req.matches(&dep_version) {
+                    continue;
+                }
+                let accepts = |r: &Release| {
+                    r.deps
+                        .iter()
+                        .filter(|(n, _)| n == dep_name)
+                        .all(|(_, req)| req.matches(&dep_version))
+                };
+                if let Some(i) = (chosen[name.as_str()] + 1..candidate.releases.len())
+                    .find(|i| accepts(&candidate.releases[*i]))
+                {
+                    chosen.insert(name, i);
+                    changed = true;
+                    break;
+                }
+                let dep = &candidates[dep_name];
+                if let Some(i) = (chosen[dep_name.as_str()] + 1..dep.releases.len())
+                    .find(|i| req.matches(&dep.releases[*i].version))
+                {
+                    chosen.insert(dep_name, i);
+                    changed = true;
+                }
+                // Otherwise the graph is split already, nothing to hold back
+            }
+        }
+    }
+
+    let mut holds = BTreeMap::new();
+    for (name, candidate) in candidates {
+        let index = chosen[name.as_str()];
+        let mut blockers = Vec::new();
+        let latest = match &candidate.stuck {
+            Some((latest, stuck)) => {
+                blockers.extend(stuck.iter().cloned());
+                latest.clone()
+            }
+            None if 0 < index => candidate.releases[0].version.clone(),
+            None => continue,
+        };
+        if 0 < index {
+            // The release passed over last is the one the blockers apply to
+            let rejected = &candidate.releases[index - 1];
+            for (other, other_candidate) in candidates {
+                if other == name {
+                    continue;
+                }
+                let other_version = version(&chosen, other);
+                if let Some(other_release) = other_candidate.releases.get(chosen[other.as_str()]) {
+                    for (_, req) in other_release
+                        .deps
+                        .iter()
+                        .filter(|(n, _)| n == name)
+                        .filter(|(_, req)| !req.matches(&rejected.version))
+                    {
+                        blockers.push(format!("{other} {other_version} requires {name} `{req}`"));
+                    }
+                }
+                for (_, req) in rejected
+                    .deps
+                    .iter()
+                    .filter(|(n, _)| n == other)
+                    .filter(|(_, req)| !req.matches(&other_version))
+                {
+                    blockers.push(format!(
+                        "{name} {} requires {other} `{req}`, which stays at {other_version}",
+                        rejected.version
+                    ));
+                }
+            }
+        }
+        holds.insert(
+            name.clone(),
+            Hold {
+                version: version(&chosen, name),
+                latest,
+                blockers,
+            },
+        );
+    }
+    holds
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn release(version: &str, deps: &[(&str, &str)]) -> Release {
+        Release {
+            version: version.parse().unwrap(),
+            deps: deps
+                .iter()
+                .map(|(n, r)| ((*n).to_owned(), r.parse().unwrap()))
+                .collect(),
+        }
+    }
+
+    fn candidate(current: &str, releases: Vec<Release>) -> Candidate {
+        Candidate {
+            current: current.parse().unwrap(),
+            releases,
+            stuck: None,
+        }
+    }
+
+    #[test]
+    fn hold_back_required_crate() {
+        let candidates = BTreeMap::from([
+            (
+                "axum".to_owned(),
+                candidate("0.7.5", vec![release("0.8.1", &[]), release("0.7.9", &[])]),
+            ),
+            (
+                "axum-extra".to_owned(),
+                candidate("0.9.3", vec![release("0.9.6", &[("axum", "^0.7")])]),
+            ),
+        ]);
+        let holds = plan(&candidates);
+        assert_eq!(holds.len(), 1);
+        let hold = &holds["axum"];
+        assert_eq!(hold.version, Version::new(0, 7, 9));
+        assert_eq!(hold.latest, Version::new(0, 8, 1));
+        assert_eq!(hold.blockers, ["axum-extra 0.9.6 requires axum `^0.7`"]);
+    }
+
+    #[test]
+    fn hold_back_requiring_crate() {
+        let candidates = BTreeMap::from([
+            (
+                "bevy".to_owned(),
+                candidate("0.14.0", vec![release("0.14.2", &[])]),
+            ),
+            (
+                "bevy_plugin".to_owned(),
+                candidate(
+                    "0.3.0",
+                    vec![
+                        release("0.5.0", &[("bevy", "^0.15")]),
+                        release("0.4.0", &[("bevy", "^0.14")]),
+                    ],
+                ),
+            ),
+        ]);
+        let holds = plan(&candidates);
+        assert_eq!(holds.len(), 1);
+        let hold = &holds["bevy_plugin"];
+        assert_eq!(hold.version, Version::new(0, 4, 0));
+        assert_eq!(
+            hold.blockers,
+            ["bevy_plugin 0.5.0 requires bevy `^0.15`, which stays at 0.14.2"]
+        );
+    }
+
+    #[test]
+    fn keep_satisfiable_upgrades() {
+        let candidates = BTreeMap::from([
+            (
+                "axum".to_owned(),
+                candidate("0.7.5", vec![release("0.8.1", &[])]),
+            ),
+            (
+                "axum-extra".to_owned(),
+                candidate("0.9.3", vec![release("0.10.0", &[("axum", "^0.8")])]),
+            ),
+        ]);
+        assert!(plan(&candidates).is_empty());
+    }
+
+    #[test]
+    fn hold_back_stuck_crate() {
+        let mut base = candidate("0.1.0", vec![release("0.1.5", &[])]);
+        base.stuck = Some((
+            Version::new(0, 2, 0),
+            vec!["two keeps base `=0.1.0`".to_owned()],
+        ));
+        let candidates = BTreeMap::from([("base".to_owned(), base)]);
+        let holds = plan(&candidates);
+        let hold = &holds["base"];
+        assert_eq!(hold.version, Version::new(0, 1, 5));
+        assert_eq!(hold.latest, Version::new(0, 2, 0));
+        assert_eq!(hold.blockers, ["two keeps base `=0.1.0`"]);
+    }
+}
@@ -0,0 +1,177 @@
+//! Commit each upgraded dependency to git on its own
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::Context as _;
+use cargo_edit::{CargoResult, ManifestTransaction, shell_status};
+
+use crate::plan::{self, PlannedEdit};
+
+/// Write and commit `edits` one dependency at a time
+///
+/// `refresh` is called with the dependency's name after its manifests are written, to bring
+/// `Cargo.lock` up to date; the manifests are restored if it fails.
+pub(crate) fn commit_each<F>(
+    workspace_root: &Path,
+    edits: &[PlannedEdit],
+    mut refresh: F,
+) -> CargoResult<()>
+where
+    F: FnMut(&str) -> CargoResult<()>,
+{
+    let groups = plan::group_by_name(edits);
+
+    let mut touched = edits
+        .iter()
+        .map(|e| workspace_root.join(&e.manifest))
+        .collect::<Vec<_>>();
+    touched.sort();
+    touched.dedup();
+    touched.extend(lockfile(workspace_root)?);
+    ensure_clean(workspace_root, &touched)?;
+
+    for (name, group) in groups {
+        let mut transaction = ManifestTransaction::new();
+        let conflicts = plan::stage_edits(&mut transaction, workspace_root, &group)?;
+        anyhow::ensure!(
+            conflicts.is_empty(),
+            "manifests changed while committing upgrades:\n  {}",
+            conflicts.join("\n  ")
+        );
+        let mut paths = transaction
+            .modified()
+            .map(|m| m.path.clone())
+            .collect::<Vec<_>>();
+        transaction.commit(|| refresh(name))?;
+
+        paths.extend(lockfile(workspace_root)?);
+        commit_paths(workspace_root, &paths, &commit_message(&group))?;
+    }
+    Ok(())
+}
+
+/// Commit `Cargo.lock` if it changed since the last commit
+pub(crate) fn commit_lockfile(workspace_root: &Path, message: &str) -> CargoResult<()> {
+    let paths = lockfile(workspace_root)?.into_iter().collect::<Vec<_>>();
+    commit_paths(workspace_root, &paths, message)
+}
+
+/// Commit message for upgrading one dependency
+///
+/// For example, `Upgrade serde 1.0.190 -> 1.0.210 in foo, bar`.
+fn commit_message(edits: &[&PlannedEdit]) -> String {
+    let first = edits.first().expect("groups are never empty");
+    let mut members = Vec::new();
+    for edit in edits {
+        if !members.contains(&edit.member.as_str()) {
+            members.push(edit.member.as_str());
+        }
+    }
+    format!(
+        "Upgrade {} {} -> {} in {}",
+        first.name,
+        first.old_req,
+        first.new_req,
+        members.join(", ")
+    )
+}
+
+/// `Cargo.lock`, unless git is told to ignore it
+fn lockfile(workspace_root: &Path) -> CargoResult<Option<PathBuf>> {
+    let path = workspace_root.join("Cargo.lock");
+    let ignored = git(workspace_root)
+        .args(["check-ignore", "--quiet", "--"])
+        .arg(&path)
+        .status()
+        .context("failed to run git")?
+        .success();
+    Ok((!ignored).then_some(path))
+}
+
+/// Refuse to touch files with uncommitted changes, as they would end up in our commits
+fn ensure_clean(workspace_root: &Path, paths: &[PathBuf]) -> CargoResult<()> {
+    let status = run_git(
+        git(workspace_root)
+            .args(["status", "--porcelain", "--untracked-files=no", "--"])
+            .args(paths),
+    )
+    .context("`--commit-each` requires a git repository")?;
+    if !status.trim().is_empty() {
+        anyhow::bail!(
+            "cannot commit each upgrade, files to be upgraded have uncommitted changes:\n{}",
+            status.trim_end()
+        );
+    }
+    Ok(())
+}
+
+fn commit_paths(workspace_root: &Path, paths: &[PathBuf], message: &str) -> CargoResult<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    run_git(git(workspace_root).arg("add").arg("--").args(paths))?;
+    let unchanged = git(workspace_root)
+        .args(["diff", "--cached", "--quiet", "--"])
+        .args(paths)
+        .status()
+        .context("failed to run git")?
+        .success();
+    if unchanged {
+        return Ok(());
+    }
+    run_git(
+        git(workspace_root)
+            .args(["commit", "--quiet", "--message", message, "--"])
+            .args(paths),
+    )?;
+    shell_status("Committed", message)?;
+    Ok(())
+}
+
+fn git(workspace_root: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(workspace_root);
+    cmd
+}
+
+fn run_git(cmd: &mut Command) -> CargoResult<String> {
+    log::trace!("Running {cmd:?}");
+    let output = cmd.output().context("failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn edit(member: &str, manifest: &str) -> PlannedEdit {
+        PlannedEdit {
+            manifest: PathBuf::from(manifest),
+            member: member.to_owned(),
+            table: vec!["dependencies".to_owned()],
+            key: "serde".to_owned(),
+            name: "serde".to_owned(),
+            old_req: "1.0.190".to_owned(),
+            new_req: "1.0.210".to_owned(),
+            tag: false,
+            index: None,
+        }
+    }
+
+    #[test]
+    fn message_lists_members_once() {
+        let foo = edit("foo", "foo/Cargo.toml");
+        let mut foo_dev = edit("foo", "foo/Cargo.toml");
+        foo_dev.table = vec!["dev-dependencies".to_owned()];
+        let bar = edit("bar", "bar/Cargo.toml");
+        let actual = commit_message(&[&foo, &foo_dev, &bar]);
+        assert_eq!(actual, "Upgrade serde 1.0.190 -> 1.0.210 in foo, bar");
+    }
+}
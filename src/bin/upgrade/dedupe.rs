@@ -0,0 +1,343 @@
+//! Suggest upgrades that collapse crates locked in several incompatible versions
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use cargo_edit::{
+    CargoResult, IndexCache, PrereleasePolicy, RustVersion, find_latest_version, registry_url,
+    shell_note, shell_status,
+};
+use cargo_metadata::{Metadata, Package, PackageId};
+use semver::{Version, VersionReq};
+use termcolor::ColorSpec;
+
+use crate::upgrade::write_table;
+
+/// A package locked next to an incompatible, newer version of itself
+struct Duplicate<'m> {
+    package: &'m Package,
+    newest: &'m Version,
+}
+
+/// Direct dependencies to upgrade together, and the duplicates that would go away
+struct Suggestion {
+    upgrades: BTreeSet<String>,
+    removes: Vec<String>,
+}
+
+/// Print which direct dependencies to upgrade to remove duplicate versions from `Cargo.lock`,
+/// those removing the most duplicates first
+pub(crate) fn suggest(
+    metadata: &Metadata,
+    root_manifest_path: &Path,
+    index: &mut IndexCache,
+    rust_version: Option<RustVersion>,
+) -> CargoResult<()> {
+    let Some(resolve) = &metadata.resolve else {
+        anyhow::bail!("cannot look for duplicate versions, the workspace failed to resolve");
+    };
+    let packages = metadata
+        .packages
+        .iter()
+        .map(|p| (&p.id, p))
+        .collect::<HashMap<_, _>>();
+    let members = metadata.workspace_members.iter().collect::<BTreeSet<_>>();
+
+    let mut dependents: HashMap<&PackageId, Vec<&PackageId>> = HashMap::new();
+    for node in &resolve.nodes {
+        for dep in &node.deps {
+            dependents.entry(&dep.pkg).or_default().push(&node.id);
+        }
+    }
+    let direct = resolve
+        .nodes
+        .iter()
+        .filter(|n| members.contains(&n.id))
+        .flat_map(|n| n.deps.iter().map(|d| &d.pkg))
+        .collect::<BTreeSet<_>>();
+
+    let duplicates = find_duplicates(&metadata.packages);
+    if duplicates.is_empty() {
+        shell_status(
+            "Checked",
+            "Cargo.lock, no crate is locked in incompatible versions",
+        )?;
+        return Ok(());
+    }
+    let mut names = duplicates
+        .iter()
+        .map(|d| d.package.name.as_str())
+        .collect::<Vec<_>>();
+    names.dedup();
+    shell_status(
+        "Found",
+        &format!("incompatible versions of {}", names.join(", ")),
+    )?;
+
+    let mut lookup = Lookup {
+        index,
+        root_manifest_path,
+        rust_version,
+    };
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    let mut stuck = Vec::new();
+    for duplicate in &duplicates {
+        let name = duplicate.package.name.as_str();
+        let label = format!("{name} {}", duplicate.package.version);
+        let mut upgrades = Some(BTreeSet::new());
+        for holder in dependents.get(&duplicate.package.id).into_iter().flatten() {
+            let released = if members.contains(holder) {
+                // The workspace depends on the old version itself
+                lookup
+                    .requires_newest(duplicate.package, None, duplicate)?
+                    .then(|| BTreeSet::from([name.to_owned()]))
+            } else if direct.contains(holder) {
+                lookup
+                    .requires_newest(packages[holder], Some(name), duplicate)?
+                    .then(|| BTreeSet::from([packages[holder].name.to_string()]))
+            } else {
+                lookup.release_transitive(
+                    packages[holder],
+                    dependents
+                        .get(holder)
+                        .map(Vec::as_slice)
+                        .unwrap_or_default(),
+                    &direct,
+                    &packages,
+                    duplicate,
+                )?
+            };
+            upgrades = upgrades.zip(released).map(|(mut all, released)| {
+                all.extend(released);
+                all
+            });
+        }
+        match upgrades {
+            Some(upgrades) if !upgrades.is_empty() => {
+                match suggestions.iter_mut().find(|s| s.upgrades == upgrades) {
+                    Some(suggestion) => suggestion.removes.push(label),
+                    None => suggestions.push(Suggestion {
+                        upgrades,
+                        removes: vec![label],
+                    }),
+                }
+            }
+            _ => stuck.push(label),
+        }
+    }
+
+    suggestions.sort_by(|a, b| {
+        b.removes
+            .len()
+            .cmp(&a.removes.len())
+            .then(a.upgrades.len().cmp(&b.upgrades.len()))
+            .then(a.upgrades.cmp(&b.upgrades))
+    });
+    print_suggestions(&suggestions)?;
+    if !stuck.is_empty() {
+        shell_note(&format!(
+            "no upgrade of a direct dependency removes {}",
+            stuck.join(", ")
+        ))?;
+    }
+    Ok(())
+}
+
+/// Packages with a newer, incompatible version also locked, oldest first
+fn find_duplicates(packages: &[Package]) -> Vec<Duplicate<'_>> {
+    let mut by_name: BTreeMap<&str, Vec<&Package>> = BTreeMap::new();
+    for package in packages {
+        if package.source.as_ref().is_some_and(|s| s.is_crates_io()) {
+            by_name
+                .entry(package.name.as_str())
+                .or_default()
+                .push(package);
+        }
+    }
+
+    let mut duplicates = Vec::new();
+    for versions in by_name.values_mut() {
+        versions.sort_by(|a, b| a.version.cmp(&b.version));
+        let newest = &versions.last().expect("never empty").version;
+        for package in versions.iter() {
+            if compatibility(&package.version) != compatibility(newest) {
+                duplicates.push(Duplicate { package, newest });
+            }
+        }
+    }
+    duplicates
+}
+
+/// The range of versions cargo considers compatible, like `1` for `1.2.0` and `0.3` for `0.3.1`
+pub(crate) fn compatibility(version: &Version) -> (u64, u64, u64) {
+    if version.major != 0 {
+        (version.major, 0, 0)
+    } else if version.minor != 0 {
+        (0, version.minor, 0)
+    } else {
+        (0, 0, version.patch)
+    }
+}
+
+/// A version of a crate in the index
+pub(crate) struct Release {
+    pub(crate) version: Version,
+    /// Package names and requirements, outside of dev-dependencies
+    pub(crate) deps: Vec<(String, VersionReq)>,
+}
+
+impl Release {
+    pub(crate) fn new(version: Version, index_version: &tame_index::IndexVersion) -> Self {
+        let deps = index_version
+            .dependencies()
+            .iter()
+            .filter(|d| d.kind() != tame_index::krate::DependencyKind::Dev)
+            .filter_map(|d| Some((d.crate_name().to_owned(), d.req.parse().ok()?)))
+            .collect();
+        Self { version, deps }
+    }
+}
+
+struct Lookup<'i, 'p> {
+    index: &'i mut IndexCache,
+    root_manifest_path: &'p Path,
+    rust_version: Option<RustVersion>,
+}
+
+impl Lookup<'_, '_> {
+    /// The latest release of `package`
+    fn latest(&mut self, package: &Package) -> CargoResult<Option<Release>> {
+        if !package.source.as_ref().is_some_and(|s| s.is_crates_io()) {
+            return Ok(None);
+        }
+        let registry = registry_url(self.root_manifest_path, None)?;
+        let Some(krate) = self.index.krate(&registry, &package.name)? else {
+            return Ok(None);
+        };
+        let Some(latest) = find_latest_version(
+            &krate.versions,
+            PrereleasePolicy::Never,
+            &semver::Prerelease::EMPTY,
+            self.rust_version,
+        )
+        .and_then(|d| d.version().and_then(|v| v.parse::<Version>().ok())) else {
+            return Ok(None);
+        };
+        let release = match krate
+            .versions
+            .iter()
+            .find(|v| v.version.parse::<Version>().is_ok_and(|v| v == latest))
+        {
+            Some(index_version) => Release::new(latest, index_version),
+            None => Release {
+                version: latest,
+                deps: Vec::new(),
+            },
+        };
+        Ok(Some(release))
+    }
+
+    /// Whether the latest `package` needs the newest version of `duplicate` instead, or drops it
+    ///
+    /// Without `dependency`, `package` is the duplicated crate itself.
+    fn requires_newest(
+        &mut self,
+        package: &Package,
+        dependency: Option<&str>,
+        duplicate: &Duplicate<'_>,
+    ) -> CargoResult<bool> {
+        let Some(latest) = self.latest(package)? else {
+            return Ok(false);
+        };
+        let Some(dependency) = dependency else {
+            return Ok(compatibility(&latest.version) == compatibility(duplicate.newest));
+        };
+        Ok(latest
+            .deps
+            .iter()
+            .filter(|(name, _)| name == dependency)
+            .all(|(_, req)| req.matches(duplicate.newest)))
+    }
+
+    /// The direct dependencies to upgrade so `holder`, a transitive dependency, lets go of
+    /// `duplicate`
+    ///
+    /// Only looks one level deep: `holder` must be depended on by direct dependencies alone.
+    fn release_transitive(
+        &mut self,
+        holder: &Package,
+        holder_dependents: &[&PackageId],
+        direct: &BTreeSet<&PackageId>,
+        packages: &HashMap<&PackageId, &Package>,
+        duplicate: &Duplicate<'_>,
+    ) -> CargoResult<Option<BTreeSet<String>>> {
+        if holder_dependents.is_empty() || !holder_dependents.iter().all(|d| direct.contains(d)) {
+            return Ok(None);
+        }
+        let Some(holder_latest) = self.latest(holder)? else {
+            return Ok(None);
+        };
+        let duplicate_name = duplicate.package.name.as_str();
+        let holder_fixed = holder_latest
+            .deps
+            .iter()
+            .filter(|(name, _)| name == duplicate_name)
+            .all(|(_, req)| req.matches(duplicate.newest));
+
+        let mut upgrades = BTreeSet::new();
+        for dependent in holder_dependents {
+            let dependent = packages[dependent];
+            let Some(dependent_latest) = self.latest(dependent)? else {
+                return Ok(None);
+            };
+            let holder_reqs = dependent_latest
+                .deps
+                .iter()
+                .filter(|(name, _)| *name == holder.name.as_str())
+                .map(|(_, req)| req)
+                .collect::<Vec<_>>();
+            let dropped = holder_reqs.is_empty();
+            let moves_on = holder_fixed
+                && holder_reqs
+                    .iter()
+                    .all(|r| r.matches(&holder_latest.version));
+            if !dropped && !moves_on {
+                return Ok(None);
+            }
+            upgrades.insert(dependent.name.to_string());
+        }
+        Ok(Some(upgrades))
+    }
+}
+
+fn print_suggestions(suggestions: &[Suggestion]) -> CargoResult<()> {
+    if suggestions.is_empty() {
+        return Ok(());
+    }
+    let rows = suggestions
+        .iter()
+        .map(|s| {
+            [
+                s.upgrades.iter().cloned().collect::<Vec<_>>().join(", "),
+                s.removes.len().to_string(),
+                s.removes.join(", "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    write_table(["upgrade", "count", "removes"], &rows, |_| ColorSpec::new())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compatibility_ranges() {
+        let v = |v: &str| compatibility(&v.parse().unwrap());
+        assert_eq!(v("1.2.0"), v("1.9.3"));
+        assert_ne!(v("1.2.0"), v("2.0.0"));
+        assert_eq!(v("0.3.0"), v("0.3.7"));
+        assert_ne!(v("0.3.0"), v("0.4.0"));
+        assert_ne!(v("0.0.1"), v("0.0.2"));
+    }
+}
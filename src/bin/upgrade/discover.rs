@@ -0,0 +1,77 @@
+//! Find the workspaces under a directory, for `--recursive-dirs`
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use cargo_edit::{CargoResult, shell_warn};
+
+use crate::upgrade::resolve_ws;
+
+/// The manifests under `dir` outside of ignored files and `target` directories, sorted
+fn find_manifests(dir: &Path) -> CargoResult<Vec<PathBuf>> {
+    let mut manifests = Vec::new();
+    let walk = ignore::WalkBuilder::new(dir)
+        .require_git(false)
+        .filter_entry(|entry| {
+            !(entry.file_type().is_some_and(|t| t.is_dir()) && entry.file_name() == "target")
+        })
+        .build();
+    for entry in walk {
+        let entry = entry.with_context(|| format!("failed to search {}", dir.display()))?;
+        if entry.file_type().is_some_and(|t| t.is_file()) && entry.file_name() == "Cargo.toml" {
+            manifests.push(entry.into_path());
+        }
+    }
+    manifests.sort();
+    Ok(manifests)
+}
+
+/// One manifest and the resolved metadata of each workspace under `dir`
+///
+/// Manifests that fail to resolve, like test fixtures, are skipped with a warning.
+pub(crate) fn workspaces(
+    dir: &Path,
+    locked: bool,
+    offline: bool,
+) -> CargoResult<Vec<(Option<PathBuf>, cargo_metadata::Metadata)>> {
+    let mut workspaces = Vec::new();
+    let mut known = BTreeSet::new();
+    for manifest_path in find_manifests(dir)? {
+        let manifest_path = dunce::canonicalize(&manifest_path).unwrap_or(manifest_path);
+        if known.contains(&manifest_path) {
+            continue;
+        }
+        let metadata = match resolve_ws(Some(&manifest_path), locked, offline) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                shell_warn(&format!("skipping {}, {err:#}", manifest_path.display()))?;
+                continue;
+            }
+        };
+        // Members of this workspace need no resolving of their own
+        known.insert(metadata.workspace_root.as_std_path().join("Cargo.toml"));
+        known.extend(
+            metadata
+                .packages
+                .iter()
+                .filter(|p| metadata.workspace_members.contains(&p.id))
+                .map(|p| p.manifest_path.clone().into_std_path_buf()),
+        );
+        if workspaces
+            .iter()
+            .any(|(_, m): &(_, cargo_metadata::Metadata)| {
+                m.workspace_root == metadata.workspace_root
+            })
+        {
+            continue;
+        }
+        workspaces.push((Some(manifest_path), metadata));
+    }
+    anyhow::ensure!(
+        !workspaces.is_empty(),
+        "no workspace found under {}",
+        dir.display()
+    );
+    Ok(workspaces)
+}
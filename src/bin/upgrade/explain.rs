@@ -0,0 +1,274 @@
+//! `--explain`: why a dependency is upgraded as it is, release by release
+
+use cargo_edit::{CargoResult, PrereleasePolicy, RustVersion, shell_status, shell_write_stdout};
+use semver::{Prerelease, Version, VersionReq};
+use termcolor::{Color, ColorSpec};
+
+use crate::upgrade::{Reason, write_cell};
+
+/// What became of a release newer than the version requirement's minimum
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    /// The requirement is upgraded to allow it
+    Selected,
+    /// The current requirement allows it already
+    Allowed,
+    Yanked,
+    /// The `--prerelease` policy leaves it out
+    Prerelease,
+    /// It requires this newer Rust
+    RustVersion(String),
+    /// Outside the requirement given with `--package`
+    Unrequested,
+    Rejected(Reason),
+}
+
+impl Verdict {
+    fn detail(&self) -> String {
+        match self {
+            Self::Selected => "selected".to_owned(),
+            Self::Allowed => "allowed by the current requirement".to_owned(),
+            Self::Yanked => "yanked".to_owned(),
+            Self::Prerelease => "a pre-release".to_owned(),
+            Self::RustVersion(rust_version) => format!("requires Rust {rust_version}"),
+            Self::Unrequested => "outside the requirement given with `--package`".to_owned(),
+            Self::Rejected(reason) => reason.detail().to_owned(),
+        }
+    }
+
+    fn remedy(&self, name: &str) -> Option<String> {
+        match self {
+            Self::Selected | Self::Allowed | Self::Yanked | Self::Unrequested => None,
+            Self::Prerelease => Some(format!("re-run with `--prerelease {name}=any`")),
+            Self::RustVersion(_) => Some(
+                "re-run with `--ignore-rust-version`, or `--bump-rust-version` to raise it"
+                    .to_owned(),
+            ),
+            Self::Rejected(reason) => reason.remedy().map(|r| r.to_owned()),
+        }
+    }
+}
+
+/// The releases of a registry dependency newer than the minimum of its requirement
+pub(crate) struct Trace {
+    /// Newest first, with the verdicts known before picking a new requirement
+    releases: Vec<(Version, Option<Verdict>)>,
+}
+
+impl Trace {
+    pub(crate) fn new(
+        versions: &[tame_index::IndexVersion],
+        current: Option<&Version>,
+        max: Option<&VersionReq>,
+        prerelease: PrereleasePolicy,
+        current_pre: &Prerelease,
+        rust_version: Option<RustVersion>,
+    ) -> Self {
+        let mut releases = versions
+            .iter()
+            .filter_map(|v| Some((v, v.version.parse::<Version>().ok()?)))
+            .filter(|(_, version)| current.is_none_or(|current| current < version))
+            .map(|(index_version, version)| {
+                let required_rust = index_version.rust_version.as_ref().filter(|required| {
+                    rust_version
+                        .zip(required.parse::<RustVersion>().ok())
+                        .is_some_and(|(rust_version, required)| rust_version < required)
+                });
+                let verdict = if index_version.yanked {
+                    Some(Verdict::Yanked)
+                } else if max.is_some_and(|max| !max.matches(&version)) {
+                    Some(Verdict::Rejected(Reason::Capped))
+                } else if !prerelease.allows(current_pre, &version) {
+                    Some(Verdict::Prerelease)
+                } else {
+                    required_rust.map(|required| Verdict::RustVersion(required.to_string()))
+                };
+                (version, verdict)
+            })
+            .collect::<Vec<_>>();
+        releases.sort_by(|(a, _), (b, _)| b.cmp(a));
+        Self { releases }
+    }
+
+    /// The verdict on each release, down to the one selected or already allowed
+    ///
+    /// `reason` is why the requirement stopped short of the latest release, if it did.
+    pub(crate) fn finish(
+        self,
+        old_req: &str,
+        new_req: &str,
+        reason: Option<Reason>,
+    ) -> Vec<(Version, Verdict)> {
+        let new_req = (old_req != new_req)
+            .then(|| VersionReq::parse(new_req).ok())
+            .flatten();
+        let old_req = VersionReq::parse(old_req).ok();
+        let mut verdicts = Vec::new();
+        for (version, verdict) in self.releases {
+            let verdict = match verdict {
+                Some(verdict) => verdict,
+                None if new_req.as_ref().is_some_and(|r| r.matches(&version)) => Verdict::Selected,
+                None if old_req.as_ref().is_some_and(|r| r.matches(&version)) => match reason {
+                    Some(reason @ (Reason::Compatible | Reason::Excluded | Reason::Held)) => {
+                        Verdict::Rejected(reason)
+                    }
+                    _ => Verdict::Allowed,
+                },
+                None => match reason {
+                    Some(
+                        reason @ (Reason::Incompatible
+                        | Reason::Pinned
+                        | Reason::Excluded
+                        | Reason::Held
+                        | Reason::MultiVersion
+                        | Reason::Blocked
+                        | Reason::Advisory),
+                    ) => Verdict::Rejected(reason),
+                    _ => Verdict::Unrequested,
+                },
+            };
+            let last = matches!(verdict, Verdict::Selected | Verdict::Allowed);
+            verdicts.push((version, verdict));
+            if last {
+                break;
+            }
+        }
+        verdicts
+    }
+}
+
+/// An occurrence of the `--explain` dependency in a manifest
+pub(crate) struct Explanation {
+    pub(crate) name: String,
+    pub(crate) member: String,
+    pub(crate) table: String,
+    pub(crate) old_req: Option<String>,
+    pub(crate) new_req: Option<String>,
+    pub(crate) reason: Option<Reason>,
+    /// Verdicts on registry releases, newest first
+    pub(crate) releases: Vec<(Version, Verdict)>,
+    /// Whether the member inherits the `[workspace.dependencies]` entry
+    pub(crate) inherited: bool,
+}
+
+impl Explanation {
+    pub(crate) fn print(&self) -> CargoResult<()> {
+        let req = self
+            .old_req
+            .as_ref()
+            .map(|req| format!(" `{req}`"))
+            .unwrap_or_default();
+        shell_status(
+            "Explaining",
+            &format!("{}{req} in {} ({})", self.name, self.member, self.table),
+        )?;
+        if self.inherited {
+            return write_line("inherits the `[workspace.dependencies]` entry", None);
+        }
+        let Some(old_req) = &self.old_req else {
+            let detail = self.reason.map(|r| r.detail()).unwrap_or("not upgradeable");
+            return write_line(detail, None);
+        };
+
+        // Runs of releases with the same verdict share a line
+        let mut runs: Vec<(String, &Verdict)> = Vec::new();
+        for run in self.releases.chunk_by(|(_, a), (_, b)| a == b) {
+            let (newest, verdict) = &run[0];
+            let versions = match run.last() {
+                Some((oldest, _)) if oldest != newest => format!("{oldest} to {newest}"),
+                _ => newest.to_string(),
+            };
+            runs.push((versions, verdict));
+        }
+        let width = runs
+            .iter()
+            .map(|(versions, _)| versions.len())
+            .max()
+            .unwrap_or(0);
+        for (versions, verdict) in &runs {
+            let mut spec = ColorSpec::new();
+            match verdict {
+                Verdict::Selected => {
+                    spec.set_fg(Some(Color::Green));
+                }
+                Verdict::Allowed => {}
+                _ => {
+                    spec.set_fg(Some(Color::Yellow));
+                }
+            }
+            shell_write_stdout("  ", &ColorSpec::new())?;
+            write_cell(versions, width, &spec)?;
+            let mut line = format!("  {}", verdict.detail());
+            if let Some(remedy) = verdict.remedy(&self.name) {
+                line.push_str(&format!(", {remedy}"));
+            }
+            shell_write_stdout(format!("{line}\n"), &ColorSpec::new())?;
+        }
+        if self.releases.is_empty() {
+            write_line(&format!("no release is newer than `{old_req}`"), None)?;
+        }
+        match &self.new_req {
+            Some(new_req) if new_req != old_req => {
+                let mut spec = ColorSpec::new();
+                spec.set_fg(Some(Color::Green));
+                write_line(&format!("upgrading `{old_req}` to `{new_req}`"), Some(spec))
+            }
+            _ => write_line(&format!("keeping `{old_req}`"), None),
+        }
+    }
+}
+
+fn write_line(line: &str, spec: Option<ColorSpec>) -> CargoResult<()> {
+    shell_write_stdout("  ", &ColorSpec::new())?;
+    shell_write_stdout(line, &spec.unwrap_or_default())?;
+    shell_write_stdout("\n", &ColorSpec::new())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trace(releases: &[(&str, Option<Verdict>)]) -> Trace {
+        Trace {
+            releases: releases
+                .iter()
+                .map(|(v, verdict)| (v.parse().unwrap(), verdict.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn stop_at_selected_release() {
+        let trace = trace(&[
+            ("2.0.0", None),
+            ("1.5.0", Some(Verdict::Yanked)),
+            ("1.4.0", None),
+            ("1.3.0", None),
+        ]);
+        let verdicts = trace.finish("1.2", "1.4", Some(Reason::Incompatible));
+        let verdicts = verdicts.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        assert_eq!(
+            verdicts,
+            [
+                Verdict::Rejected(Reason::Incompatible),
+                Verdict::Yanked,
+                Verdict::Selected,
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_every_release_when_held() {
+        let trace = trace(&[("2.0.0", None), ("1.3.0", None)]);
+        let verdicts = trace.finish("1.2", "1.2", Some(Reason::Held));
+        let verdicts = verdicts.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        assert_eq!(
+            verdicts,
+            [
+                Verdict::Rejected(Reason::Held),
+                Verdict::Rejected(Reason::Held),
+            ]
+        );
+    }
+}
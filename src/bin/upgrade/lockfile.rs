@@ -0,0 +1,289 @@
+//! What moved in `Cargo.lock` while upgrading
+
+use std::collections::BTreeSet;
+
+use cargo_edit::{CargoResult, shell_status, shell_warn};
+use semver::Version;
+use termcolor::{Color, ColorSpec};
+
+use crate::dedupe::compatibility;
+use crate::upgrade::write_table;
+
+/// The packages locked at one point in time
+pub(crate) struct Snapshot {
+    packages: Vec<Locked>,
+    /// Names of the workspace members' dependencies
+    direct: BTreeSet<String>,
+}
+
+impl Snapshot {
+    pub(crate) fn new(metadata: &cargo_metadata::Metadata) -> Self {
+        let members = metadata.workspace_members.iter().collect::<BTreeSet<_>>();
+        let packages = metadata
+            .packages
+            .iter()
+            .filter(|p| !members.contains(&p.id))
+            .map(|p| Locked {
+                name: p.name.to_string(),
+                version: p.version.clone(),
+                source: p.source.as_ref().map(|s| s.repr.clone()),
+            })
+            .collect();
+        let direct = metadata
+            .packages
+            .iter()
+            .filter(|p| members.contains(&p.id))
+            .flat_map(|p| p.dependencies.iter().map(|d| d.name.clone()))
+            .collect();
+        Self { packages, direct }
+    }
+
+    /// Changes from `self` to `after`, direct dependencies first
+    pub(crate) fn diff(&self, after: &Self) -> Vec<LockChange> {
+        let names = self
+            .packages
+            .iter()
+            .chain(&after.packages)
+            .map(|p| p.name.as_str())
+            .collect::<BTreeSet<_>>();
+        let mut changes = Vec::new();
+        for name in names {
+            let direct = self.direct.contains(name) || after.direct.contains(name);
+            // `p` is locked as `name` here but not in `others`
+            let missing_from = |others: &[Locked], p: &&Locked| {
+                p.name == name
+                    && !others
+                        .iter()
+                        .any(|o| o.name == p.name && o.version == p.version && o.source == p.source)
+            };
+            let mut removed = self
+                .packages
+                .iter()
+                .filter(|p| missing_from(&after.packages, p))
+                .collect::<Vec<_>>();
+            let mut added = after
+                .packages
+                .iter()
+                .filter(|p| missing_from(&self.packages, p))
+                .collect::<Vec<_>>();
+            removed.sort_by(|a, b| a.version.cmp(&b.version));
+            added.sort_by(|a, b| a.version.cmp(&b.version));
+
+            // Pair versions within the same compatibility range, then whatever is left over
+            let mut pairs = Vec::new();
+            removed.retain(|old| {
+                match added
+                    .iter()
+                    .position(|new| compatibility(&old.version) == compatibility(&new.version))
+                {
+                    Some(i) => {
+                        pairs.push((*old, added.remove(i)));
+                        false
+                    }
+                    None => true,
+                }
+            });
+            let leftover = removed.len().min(added.len());
+            pairs.extend(removed.drain(..leftover).zip(added.drain(..leftover)));
+
+            for (old, new) in pairs {
+                changes.push(LockChange::new(name, direct, Some(old), Some(new)));
+            }
+            for old in removed {
+                changes.push(LockChange::new(name, direct, Some(old), None));
+            }
+            for new in added {
+                changes.push(LockChange::new(name, direct, None, Some(new)));
+            }
+        }
+        changes.sort_by(|a, b| b.direct.cmp(&a.direct).then(a.name.cmp(&b.name)));
+        changes
+    }
+}
+
+struct Locked {
+    name: String,
+    version: Version,
+    source: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ChangeKind {
+    Added,
+    Removed,
+    Upgraded,
+    Downgraded,
+    /// Same version from another source
+    Replaced,
+}
+
+impl ChangeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Upgraded => "upgraded",
+            Self::Downgraded => "downgraded",
+            Self::Replaced => "replaced",
+        }
+    }
+}
+
+#[derive(Debug, serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct LockChange {
+    pub(crate) name: String,
+    /// Whether a workspace member depends on it
+    pub(crate) direct: bool,
+    pub(crate) kind: ChangeKind,
+    pub(crate) old_version: Option<Version>,
+    pub(crate) new_version: Option<Version>,
+    pub(crate) old_source: Option<String>,
+    pub(crate) new_source: Option<String>,
+}
+
+impl LockChange {
+    fn new(name: &str, direct: bool, old: Option<&Locked>, new: Option<&Locked>) -> Self {
+        let kind = match (old, new) {
+            (Some(old), Some(new)) if old.version < new.version => ChangeKind::Upgraded,
+            (Some(old), Some(new)) if new.version < old.version => ChangeKind::Downgraded,
+            (Some(_), Some(_)) => ChangeKind::Replaced,
+            (Some(_), None) => ChangeKind::Removed,
+            (None, _) => ChangeKind::Added,
+        };
+        Self {
+            name: name.to_owned(),
+            direct,
+            kind,
+            old_version: old.map(|p| p.version.clone()),
+            new_version: new.map(|p| p.version.clone()),
+            old_source: old.and_then(|p| p.source.clone()),
+            new_source: new.and_then(|p| p.source.clone()),
+        }
+    }
+
+    /// Whether the package now comes from somewhere else
+    pub(crate) fn source_changed(&self) -> bool {
+        self.old_version.is_some()
+            && self.new_version.is_some()
+            && self.old_source != self.new_source
+    }
+
+    pub(crate) fn dependency(&self) -> &'static str {
+        if self.direct { "direct" } else { "transitive" }
+    }
+
+    pub(crate) fn change(&self) -> String {
+        if self.source_changed() && self.kind != ChangeKind::Replaced {
+            format!("{}, new source", self.kind.as_str())
+        } else if self.source_changed() {
+            "new source".to_owned()
+        } else {
+            self.kind.as_str().to_owned()
+        }
+    }
+}
+
+/// Print `changes` as a table, followed by the sources that changed
+pub(crate) fn print_changes(changes: &[LockChange]) -> CargoResult<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let direct = changes.iter().filter(|c| c.direct).count();
+    shell_status(
+        "Changed",
+        &format!(
+            "Cargo.lock, {direct} direct and {} transitive dependencies",
+            changes.len() - direct
+        ),
+    )?;
+
+    let version = |v: &Option<Version>| v.as_ref().map(|v| v.to_string());
+    let rows = changes
+        .iter()
+        .map(|c| {
+            [
+                c.name.clone(),
+                c.dependency().to_owned(),
+                version(&c.old_version).unwrap_or_else(|| "-".to_owned()),
+                version(&c.new_version).unwrap_or_else(|| "-".to_owned()),
+                c.change(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    write_table(["name", "dependency", "old", "new", "change"], &rows, |i| {
+        let change = &changes[i];
+        let mut spec = ColorSpec::new();
+        if change.source_changed() {
+            spec.set_fg(Some(Color::Red));
+        } else if matches!(change.kind, ChangeKind::Downgraded | ChangeKind::Removed) {
+            spec.set_fg(Some(Color::Yellow));
+        }
+        spec
+    })?;
+
+    for change in changes.iter().filter(|c| c.source_changed()) {
+        shell_warn(&format!(
+            "{} is now locked from {} instead of {}",
+            change.name,
+            change.new_source.as_deref().unwrap_or("a path"),
+            change.old_source.as_deref().unwrap_or("a path"),
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn locked(name: &str, version: &str, source: &str) -> Locked {
+        Locked {
+            name: name.to_owned(),
+            version: version.parse().unwrap(),
+            source: Some(source.to_owned()),
+        }
+    }
+
+    const REGISTRY: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+    #[test]
+    fn diff_categorizes_changes() {
+        let before = Snapshot {
+            packages: vec![
+                locked("toml", "0.5.11", REGISTRY),
+                locked("syn", "1.0.109", REGISTRY),
+                locked("syn", "2.0.10", REGISTRY),
+                locked("serde", "1.0.190", REGISTRY),
+                locked("old", "0.1.0", REGISTRY),
+            ],
+            direct: ["toml".to_owned()].into(),
+        };
+        let after = Snapshot {
+            packages: vec![
+                locked("toml", "0.7.8", REGISTRY),
+                locked("syn", "1.0.109", REGISTRY),
+                locked("syn", "2.0.50", REGISTRY),
+                locked("serde", "1.0.190", "git+https://github.com/serde-rs/serde"),
+                locked("toml_datetime", "0.6.3", REGISTRY),
+            ],
+            direct: ["toml".to_owned()].into(),
+        };
+        let actual = before
+            .diff(&after)
+            .iter()
+            .map(|c| format!("{} {} {}", c.dependency(), c.name, c.change()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actual,
+            [
+                "direct toml upgraded",
+                "transitive old removed",
+                "transitive serde new source",
+                "transitive syn upgraded",
+                "transitive toml_datetime added",
+            ]
+        );
+    }
+}
@@ -11,8 +11,22 @@
     unused_qualifications
 )]
 
+mod advisory;
+mod annotation;
 mod cli;
+mod coherent;
+mod commit;
+mod dedupe;
+mod discover;
+mod explain;
+mod lockfile;
+mod patch;
+mod plan;
+mod select;
+mod staleness;
+mod summary;
 mod upgrade;
+mod verify;
 
 use std::process;
 
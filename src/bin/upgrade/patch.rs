@@ -0,0 +1,309 @@
+//! Git tags pinned in `[patch]` and `[replace]`, and patches cargo no longer uses
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Context as _;
+use cargo_edit::{CargoResult, LocalManifest, PrereleasePolicy, set_dep_tag};
+
+use crate::annotation::Annotation;
+
+/// A `[patch]` or `[replace]` entry pinned to a git tag
+pub(crate) struct TagPin {
+    pub(crate) table: Vec<String>,
+    pub(crate) key: String,
+    /// Package name of the patched crate
+    pub(crate) name: String,
+    pub(crate) git: String,
+    pub(crate) tag: String,
+    /// The `# cargo-upgrade:` comment next to the entry
+    pub(crate) annotation: Option<Annotation>,
+}
+
+/// Newer tags of a [`TagPin`]'s repository
+pub(crate) struct TagVersions {
+    pub(crate) compatible: Option<String>,
+    pub(crate) latest: Option<String>,
+    /// Whether an annotation's upper bound kept newer tags out
+    pub(crate) capped: bool,
+}
+
+/// Whether `item` is a patch entry pinned to a git tag
+pub(crate) fn is_tag_pin(item: &toml_edit::Item) -> bool {
+    item.get("git").and_then(|i| i.as_str()).is_some()
+        && item.get("tag").and_then(|i| i.as_str()).is_some()
+}
+
+/// All entries in `manifest`'s patch tables that are pinned to a git tag
+pub(crate) fn tag_pins(manifest: &LocalManifest) -> CargoResult<Vec<TagPin>> {
+    let mut pins = Vec::new();
+    for table_path in manifest.get_patch_table_paths() {
+        let Some(table) = table_path
+            .iter()
+            .try_fold(manifest.data.as_item(), |item, key| item.get(key))
+        else {
+            continue;
+        };
+        let Some(table) = table.as_table_like() else {
+            continue;
+        };
+        for (key, item) in table.iter() {
+            if !is_tag_pin(item) {
+                continue;
+            }
+            let (leaf, _) = table.get_key_value(key).expect("iterated keys exist");
+            let annotation = Annotation::find(leaf.leaf_decor(), item).with_context(|| {
+                format!(
+                    "invalid annotation on `{key}` in `{}`",
+                    table_path.join(".")
+                )
+            })?;
+            let name = if table_path == ["replace"] {
+                replaced_name(key).to_owned()
+            } else {
+                item.get("package")
+                    .and_then(|i| i.as_str())
+                    .unwrap_or(key)
+                    .to_owned()
+            };
+            pins.push(TagPin {
+                table: table_path.clone(),
+                key: key.to_owned(),
+                name,
+                git: item["git"]
+                    .as_str()
+                    .expect("checked by is_tag_pin")
+                    .to_owned(),
+                tag: item["tag"]
+                    .as_str()
+                    .expect("checked by is_tag_pin")
+                    .to_owned(),
+                annotation,
+            });
+        }
+    }
+    Ok(pins)
+}
+
+/// Find newer tags for `pin` in its repository, up to `max`
+///
+/// Returns `None` if the pinned tag doesn't name a version.
+pub(crate) fn find_tags(
+    pin: &TagPin,
+    prerelease: PrereleasePolicy,
+    max: Option<&semver::VersionReq>,
+) -> CargoResult<Option<TagVersions>> {
+    let Some((prefix, current)) = split_tag(&pin.tag) else {
+        return Ok(None);
+    };
+
+    let mut cmd = Command::new("git");
+    cmd.args(["ls-remote", "--tags", "--refs", &pin.git]);
+    log::trace!("Running {cmd:?}");
+    let output = cmd.output().context("failed to run git")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "failed to list tags of {}: {}",
+            pin.git,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let tags = stdout
+        .lines()
+        .filter_map(|l| l.split_once("refs/tags/"))
+        .map(|(_, tag)| tag.to_owned())
+        .collect::<Vec<_>>();
+    Ok(Some(newer_tags(prefix, &current, &tags, prerelease, max)))
+}
+
+fn newer_tags(
+    prefix: &str,
+    current: &semver::Version,
+    tags: &[String],
+    prerelease: PrereleasePolicy,
+    max: Option<&semver::VersionReq>,
+) -> TagVersions {
+    let compatible_req =
+        semver::VersionReq::parse(&format!("^{current}")).expect("versions are valid requirements");
+    let candidates = tags
+        .iter()
+        .filter_map(|tag| {
+            let (tag_prefix, version) = split_tag(tag)?;
+            (tag_prefix == prefix).then_some((tag, version))
+        })
+        .filter(|(_, v)| current <= v && prerelease.allows(&current.pre, v))
+        .collect::<Vec<_>>();
+    let (candidates, above_max) = candidates
+        .into_iter()
+        .partition::<Vec<_>, _>(|(_, v)| max.is_none_or(|max| max.matches(v)));
+
+    let compatible = candidates
+        .iter()
+        .filter(|(_, v)| compatible_req.matches(v))
+        .max_by_key(|(_, v)| v)
+        .map(|(tag, _)| (*tag).clone());
+    let latest = candidates
+        .iter()
+        .max_by_key(|(_, v)| v)
+        .map(|(tag, _)| (*tag).clone());
+    TagVersions {
+        compatible,
+        latest,
+        capped: !above_max.is_empty(),
+    }
+}
+
+/// Point the tag pin at `key` in `table` to `new_tag`, returning the entry's key afterwards
+///
+/// `[replace]` keys name the version being replaced, so they follow the tag.
+pub(crate) fn set_tag(
+    table_path: &[String],
+    table: &mut toml_edit::Item,
+    key: &str,
+    new_tag: &str,
+) -> CargoResult<String> {
+    let item = table
+        .get_mut(key)
+        .with_context(|| format!("`{key}` is missing"))?;
+    set_dep_tag(item, new_tag)?;
+
+    if table_path != ["replace"] {
+        return Ok(key.to_owned());
+    }
+    let Some(new_key) = replaced_key(key, new_tag) else {
+        return Ok(key.to_owned());
+    };
+    let table = table
+        .as_table_mut()
+        .context("`[replace]` must be a table to update its keys")?;
+    rename_key(table, key, &new_key);
+    Ok(new_key)
+}
+
+/// Crate names for the patches cargo recorded as unused in `Cargo.lock`
+pub(crate) fn unused_patches(lock_path: &Path) -> CargoResult<Vec<String>> {
+    let Ok(content) = std::fs::read_to_string(lock_path) else {
+        return Ok(Vec::new());
+    };
+    let lock = content
+        .parse::<toml::Table>()
+        .with_context(|| format!("failed to parse {}", lock_path.display()))?;
+    let unused = lock
+        .get("patch")
+        .and_then(|p| p.get("unused"))
+        .and_then(|u| u.as_array())
+        .map(|u| u.as_slice())
+        .unwrap_or_default();
+    Ok(unused
+        .iter()
+        .filter_map(|p| {
+            let name = p.get("name")?.as_str()?;
+            let version = p.get("version")?.as_str()?;
+            Some(format!("{name} {version}"))
+        })
+        .collect())
+}
+
+/// Split a tag like `v1.2.3` or `foo-1.2` into its prefix and version
+pub(crate) fn split_tag(tag: &str) -> Option<(&str, semver::Version)> {
+    tag.char_indices()
+        .filter(|(i, c)| {
+            c.is_ascii_digit() && !tag[..*i].ends_with(|p: char| p.is_ascii_digit() || p == '.')
+        })
+        .find_map(|(i, _)| {
+            let version = &tag[i..];
+            let version = semver::Version::parse(version)
+                .or_else(|_| semver::Version::parse(&format!("{version}.0")))
+                .ok()?;
+            Some((&tag[..i], version))
+        })
+}
+
+/// The package name in a `[replace]` key, like `foo` in `foo:1.0.0`
+fn replaced_name(key: &str) -> &str {
+    let name = key.rsplit_once('#').map(|(_, n)| n).unwrap_or(key);
+    match name.rfind([':', '@']) {
+        Some(i) if semver::Version::parse(&name[i + 1..]).is_ok() => &name[..i],
+        _ => name,
+    }
+}
+
+/// `key` with its version replaced by the one in `new_tag`
+fn replaced_key(key: &str, new_tag: &str) -> Option<String> {
+    let (_, version) = split_tag(new_tag)?;
+    let i = key.rfind([':', '@'])?;
+    semver::Version::parse(&key[i + 1..]).ok()?;
+    Some(format!("{}{version}", &key[..=i]))
+}
+
+/// Rename `old` to `new`, keeping its position and formatting
+fn rename_key(table: &mut toml_edit::Table, old: &str, new: &str) {
+    let order = table
+        .iter()
+        .map(|(k, _)| if k == old { new } else { k }.to_owned())
+        .collect::<Vec<_>>();
+    let Some((old_key, item)) = table.remove_entry(old) else {
+        return;
+    };
+    let mut new_key = toml_edit::Key::new(new);
+    *new_key.leaf_decor_mut() = old_key.leaf_decor().clone();
+    table.insert_formatted(&new_key, item);
+    let position = |k: &toml_edit::Key| order.iter().position(|o| o == k.get());
+    table.sort_values_by(|k1, _, k2, _| position(k1).cmp(&position(k2)));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_tags() {
+        assert_eq!(
+            split_tag("v1.2.3"),
+            Some(("v", semver::Version::new(1, 2, 3)))
+        );
+        assert_eq!(
+            split_tag("foo-v0.4"),
+            Some(("foo-v", semver::Version::new(0, 4, 0)))
+        );
+        assert_eq!(
+            split_tag("sha2-0.10.8"),
+            Some(("sha2-", semver::Version::new(0, 10, 8)))
+        );
+        assert_eq!(split_tag("nightly"), None);
+    }
+
+    #[test]
+    fn newer_tags_follow_prefix_and_compatibility() {
+        let tags = ["v1.2.3", "v1.4.0", "v2.0.0", "v2.1.0-rc.1", "other-v9.0.0"].map(str::to_owned);
+        let current = semver::Version::new(1, 2, 3);
+        let actual = newer_tags("v", &current, &tags, PrereleasePolicy::SameChannel, None);
+        assert_eq!(actual.compatible.as_deref(), Some("v1.4.0"));
+        assert_eq!(actual.latest.as_deref(), Some("v2.0.0"));
+    }
+
+    #[test]
+    fn replace_keys_follow_tag() {
+        assert_eq!(replaced_name("foo:1.0.0"), "foo");
+        assert_eq!(replaced_name("https://github.com/a/b#foo@1.0.0"), "foo");
+        assert_eq!(
+            replaced_key("foo:1.0.0", "v1.1.0").as_deref(),
+            Some("foo:1.1.0")
+        );
+        assert_eq!(replaced_key("foo", "v1.1.0"), None);
+    }
+
+    #[test]
+    fn rename_keeps_position() {
+        let mut doc = "[replace]\n\"a:1.0.0\" = { git = \"a\", tag = \"v1.0.0\" }\nb = 1\n"
+            .parse::<toml_edit::DocumentMut>()
+            .unwrap();
+        let table = doc["replace"].as_table_mut().unwrap();
+        rename_key(table, "a:1.0.0", "a:1.1.0");
+        assert_eq!(
+            doc.to_string(),
+            "[replace]\n\"a:1.1.0\" = { git = \"a\", tag = \"v1.0.0\" }\nb = 1\n"
+        );
+    }
+}
@@ -0,0 +1,289 @@
+//! Saved upgrade plans, for reviewing edits before they are applied
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use cargo_edit::{
+    CargoResult, EditJournal, ManifestTransaction, get_dep_version, set_dep_version, shell_status,
+};
+use indexmap::IndexMap;
+
+use crate::patch;
+use crate::upgrade::revalidate_ws;
+
+const PLAN_FORMAT_VERSION: u32 = 1;
+
+/// Edits recorded by `--plan-out`, to be replayed by `--apply`
+#[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct UpgradePlan {
+    version: u32,
+    #[serde(default, rename = "edit")]
+    edits: Vec<PlannedEdit>,
+}
+
+impl UpgradePlan {
+    pub(crate) fn new() -> Self {
+        Self {
+            version: PLAN_FORMAT_VERSION,
+            edits: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, edit: PlannedEdit) {
+        self.edits.push(edit);
+    }
+
+    pub(crate) fn edits(&self) -> &[PlannedEdit] {
+        &self.edits
+    }
+
+    pub(crate) fn load(path: &Path) -> CargoResult<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read upgrade plan {}", path.display()))?;
+        let plan = toml::from_str::<Self>(&content)
+            .with_context(|| format!("invalid upgrade plan {}", path.display()))?;
+        anyhow::ensure!(
+            plan.version == PLAN_FORMAT_VERSION,
+            "unsupported upgrade plan version {} in {}, expected {}",
+            plan.version,
+            path.display(),
+            PLAN_FORMAT_VERSION
+        );
+        Ok(plan)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> CargoResult<()> {
+        let content = toml::to_string(self).context("failed to serialize upgrade plan")?;
+        std::fs::write(path, content)
+            .with_context(|| format!("failed to write upgrade plan {}", path.display()))
+    }
+}
+
+/// A single version requirement change
+#[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct PlannedEdit {
+    /// Manifest to edit, relative to the workspace root
+    pub(crate) manifest: PathBuf,
+    /// Package the manifest belongs to
+    pub(crate) member: String,
+    /// Path to the dependency table within the manifest
+    pub(crate) table: Vec<String>,
+    /// Key of the dependency within `table`
+    pub(crate) key: String,
+    /// Package name of the dependency
+    pub(crate) name: String,
+    pub(crate) old_req: String,
+    pub(crate) new_req: String,
+    /// Whether `old-req` and `new-req` are git tags, for `[patch]` and `[replace]` entries
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) tag: bool,
+    /// What the registry reported when the plan was made
+    pub(crate) index: Option<IndexSnapshot>,
+}
+
+#[derive(Debug, serde_derive::Serialize, serde_derive::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct IndexSnapshot {
+    pub(crate) registry: String,
+    pub(crate) compatible: Option<String>,
+    pub(crate) latest: Option<String>,
+}
+
+/// Path of `manifest_path`, as recorded in a plan
+pub(crate) fn relative_manifest_path(manifest_path: &Path, workspace_root: &Path) -> PathBuf {
+    pathdiff::diff_paths(manifest_path, workspace_root).unwrap_or_else(|| manifest_path.to_owned())
+}
+
+/// Group `edits` by the dependency they upgrade, in plan order
+pub(crate) fn group_by_name(edits: &[PlannedEdit]) -> IndexMap<&str, Vec<&PlannedEdit>> {
+    let mut groups: IndexMap<&str, Vec<&PlannedEdit>> = IndexMap::new();
+    for edit in edits {
+        groups.entry(edit.name.as_str()).or_default().push(edit);
+    }
+    groups
+}
+
+/// Stage `edits` on top of what `transaction` already holds
+///
+/// Edits whose recorded old requirement no longer matches the manifest are skipped and reported
+/// back as conflicts.
+pub(crate) fn stage_edits(
+    transaction: &mut ManifestTransaction,
+    workspace_root: &Path,
+    edits: &[&PlannedEdit],
+) -> CargoResult<Vec<String>> {
+    let mut conflicts = Vec::new();
+    for edit in edits {
+        let relpath = edit.manifest.as_path();
+        let table_name = edit.table.join(".");
+        let manifest = transaction.manifest_mut(&workspace_root.join(relpath))?;
+        let table = manifest.get_table_mut(&edit.table).ok();
+        let Some(table) = table.filter(|t| t.get(&edit.key).is_some()) else {
+            conflicts.push(format!(
+                "{}: `{}` is no longer in `{}`",
+                relpath.display(),
+                edit.key,
+                table_name
+            ));
+            continue;
+        };
+        let dep_item = &table[&edit.key];
+        let current = if edit.tag {
+            dep_item
+                .get("tag")
+                .and_then(|t| t.as_str())
+                .ok_or_else(|| anyhow::format_err!("Missing tag field"))
+        } else {
+            get_dep_version(dep_item)
+        };
+        match current {
+            Ok(current) if current == edit.old_req => {}
+            Ok(current) => {
+                conflicts.push(format!(
+                    "{}: `{}` in `{}` is {}, plan expected {}",
+                    relpath.display(),
+                    edit.key,
+                    table_name,
+                    current,
+                    edit.old_req
+                ));
+                continue;
+            }
+            Err(err) => {
+                conflicts.push(format!(
+                    "{}: `{}` in `{}` is unsupported: {}",
+                    relpath.display(),
+                    edit.key,
+                    table_name,
+                    err
+                ));
+                continue;
+            }
+        }
+        if edit.tag {
+            patch::set_tag(&edit.table, table, &edit.key, &edit.new_req)?;
+        } else {
+            set_dep_version(&mut table[&edit.key], &edit.new_req)?;
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Replay a saved plan against the workspace of `metadata`
+///
+/// Nothing is written unless every recorded old requirement still matches the manifests.
+pub(crate) fn apply(
+    plan_path: &Path,
+    metadata: &cargo_metadata::Metadata,
+    root_manifest_path: &Path,
+    dry_run: bool,
+    diff: bool,
+    locked: bool,
+) -> CargoResult<()> {
+    let plan = UpgradePlan::load(plan_path)?;
+    let workspace_root = metadata.workspace_root.as_std_path();
+
+    // The plan file may come from elsewhere, only let it edit this workspace's manifests
+    let manifests = metadata
+        .workspace_packages()
+        .into_iter()
+        .map(|p| p.manifest_path.as_std_path())
+        .chain([root_manifest_path])
+        .collect::<Vec<_>>();
+    for edit in &plan.edits {
+        if !manifests.contains(&workspace_root.join(&edit.manifest).as_path()) {
+            anyhow::bail!(
+                "upgrade plan edits {}, which is not a manifest of this workspace",
+                edit.manifest.display()
+            );
+        }
+    }
+
+    let edits = plan.edits.iter().collect::<Vec<_>>();
+    let mut transaction = ManifestTransaction::new();
+    let conflicts = stage_edits(&mut transaction, workspace_root, &edits)?;
+    if !conflicts.is_empty() {
+        anyhow::bail!(
+            "manifests changed since the upgrade plan was made:\n  {}",
+            conflicts.join("\n  ")
+        );
+    }
+    for edit in &edits {
+        shell_status(
+            "Upgrading",
+            &format!(
+                "{} from {} to {} in {}",
+                edit.key,
+                edit.old_req,
+                edit.new_req,
+                edit.manifest.display()
+            ),
+        )?;
+    }
+
+    if !edits.is_empty() && locked {
+        anyhow::bail!("cannot upgrade due to `--locked`");
+    }
+    if diff {
+        for manifest in transaction.modified() {
+            manifest.write_diff()?;
+        }
+    }
+    if !dry_run {
+        let mut journal = EditJournal::new(
+            metadata.target_directory.as_std_path(),
+            "cargo upgrade --apply",
+        );
+        for manifest in transaction.modified() {
+            journal.record(&manifest.path)?;
+        }
+        journal.record(&workspace_root.join("Cargo.lock"))?;
+        transaction.commit(|| {
+            let offline = true;
+            revalidate_ws(metadata, root_manifest_path, locked, offline)?;
+            Ok(())
+        })?;
+        journal.save()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plan_roundtrip() {
+        let mut plan = UpgradePlan::new();
+        plan.push(PlannedEdit {
+            manifest: PathBuf::from("Cargo.toml"),
+            member: "foo".to_owned(),
+            table: vec![
+                "target".to_owned(),
+                "cfg(windows)".to_owned(),
+                "dependencies".to_owned(),
+            ],
+            key: "serde".to_owned(),
+            name: "serde".to_owned(),
+            old_req: "1.0.190".to_owned(),
+            new_req: "1.0.210".to_owned(),
+            tag: false,
+            index: Some(IndexSnapshot {
+                registry: "sparse+https://index.crates.io/".to_owned(),
+                compatible: Some("1.0.210".to_owned()),
+                latest: Some("1.0.210".to_owned()),
+            }),
+        });
+
+        let content = toml::to_string(&plan).unwrap();
+        let actual = toml::from_str::<UpgradePlan>(&content).unwrap();
+        assert_eq!(actual.version, PLAN_FORMAT_VERSION);
+        assert_eq!(actual.edits.len(), 1);
+        assert_eq!(actual.edits[0].table, plan.edits[0].table);
+        assert_eq!(actual.edits[0].old_req, "1.0.190");
+        assert_eq!(actual.edits[0].new_req, "1.0.210");
+    }
+}
@@ -0,0 +1,228 @@
+//! Dependencies picked by `--package` and `--exclude`, and members by `--member`
+
+use std::collections::BTreeSet;
+
+use anyhow::Context as _;
+use cargo_edit::{CargoResult, CrateSpec};
+
+/// A crate name, a glob like `aws-sdk-*` or a regex like `/^aws-sdk-(s3|ec2)$/`
+pub(crate) enum NamePattern {
+    Exact(String),
+    Glob(String, globset::GlobMatcher),
+    Regex(regex::Regex),
+}
+
+impl NamePattern {
+    pub(crate) fn parse(text: &str) -> CargoResult<Self> {
+        if let Some(regex) = text
+            .strip_prefix('/')
+            .and_then(|text| text.strip_suffix('/'))
+        {
+            let regex =
+                regex::Regex::new(regex).with_context(|| format!("invalid regex `{text}`"))?;
+            Ok(Self::Regex(regex))
+        } else if text.contains(['*', '?', '[']) {
+            let glob = globset::Glob::new(text)
+                .with_context(|| format!("invalid glob `{text}`"))?
+                .compile_matcher();
+            Ok(Self::Glob(text.to_owned(), glob))
+        } else {
+            Ok(Self::Exact(CrateSpec::resolve(text)?.name))
+        }
+    }
+
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Exact(exact) => exact == name,
+            Self::Glob(_, glob) => glob.is_match(name),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+impl std::fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact(name) => name.fmt(f),
+            Self::Glob(glob, _) => glob.fmt(f),
+            Self::Regex(regex) => write!(f, "/{regex}/"),
+        }
+    }
+}
+
+/// A `--package` argument: a name pattern, with a version requirement for every match
+struct PackageSpec {
+    pattern: NamePattern,
+    version_req: Option<String>,
+}
+
+impl PackageSpec {
+    fn parse(text: &str) -> CargoResult<Self> {
+        // The version follows the closing `/` of a regex, which may itself contain `@`
+        let split = match text.strip_prefix('/') {
+            Some(rest) => rest.rfind('/').map(|i| i + 2),
+            None => Some(0),
+        };
+        let (pattern, version_req) =
+            match split.and_then(|split| text[split..].find('@').map(|i| split + i)) {
+                Some(at) => (&text[..at], Some(&text[at + 1..])),
+                None => (text, None),
+            };
+        if let Some(version_req) = version_req {
+            semver::VersionReq::parse(version_req)
+                .with_context(|| format!("Invalid version requirement `{version_req}`"))?;
+        }
+        Ok(Self {
+            pattern: NamePattern::parse(pattern)?,
+            version_req: version_req.map(|v| v.to_owned()),
+        })
+    }
+}
+
+/// The dependencies to upgrade, from `--package` and `--exclude`
+pub(crate) struct Selection {
+    packages: Vec<PackageSpec>,
+    excludes: Vec<NamePattern>,
+}
+
+impl Selection {
+    pub(crate) fn new(packages: &[String], excludes: &[String]) -> CargoResult<Self> {
+        Ok(Self {
+            packages: packages
+                .iter()
+                .map(|p| PackageSpec::parse(p))
+                .collect::<CargoResult<_>>()?,
+            excludes: excludes
+                .iter()
+                .map(|e| NamePattern::parse(e))
+                .collect::<CargoResult<_>>()?,
+        })
+    }
+
+    /// Whether the dependency `name` may be upgraded
+    pub(crate) fn selects(&self, name: &str) -> bool {
+        let included =
+            self.packages.is_empty() || self.packages.iter().any(|p| p.pattern.matches(name));
+        included && !self.excludes.iter().any(|e| e.matches(name))
+    }
+
+    /// The version requirement given for `name`, from the first `--package` matching it
+    pub(crate) fn version_req(&self, name: &str) -> Option<&str> {
+        self.packages
+            .iter()
+            .find(|p| p.pattern.matches(name))
+            .and_then(|p| p.version_req.as_deref())
+    }
+
+    /// The `--package` patterns that match none of `names`
+    pub(crate) fn unmatched(&self, names: &BTreeSet<String>) -> Vec<String> {
+        self.packages
+            .iter()
+            .filter(|p| !names.iter().any(|name| p.pattern.matches(name)))
+            .map(|p| p.pattern.to_string())
+            .collect()
+    }
+}
+
+/// The workspace members whose manifests to upgrade, from `--member` and `--exclude-member`
+pub(crate) struct MemberSelection {
+    members: Vec<NamePattern>,
+    excludes: Vec<NamePattern>,
+}
+
+impl MemberSelection {
+    pub(crate) fn new(members: &[String], excludes: &[String]) -> CargoResult<Self> {
+        Ok(Self {
+            members: members
+                .iter()
+                .map(|m| NamePattern::parse(m))
+                .collect::<CargoResult<_>>()?,
+            excludes: excludes
+                .iter()
+                .map(|e| NamePattern::parse(e))
+                .collect::<CargoResult<_>>()?,
+        })
+    }
+
+    /// Also select the member `name`
+    pub(crate) fn push(&mut self, name: String) {
+        self.members.push(NamePattern::Exact(name));
+    }
+
+    /// Whether every member is selected
+    pub(crate) fn is_all(&self) -> bool {
+        self.members.is_empty() && self.excludes.is_empty()
+    }
+
+    pub(crate) fn selects(&self, name: &str) -> bool {
+        let included = self.members.is_empty() || self.members.iter().any(|m| m.matches(name));
+        included && !self.excludes.iter().any(|e| e.matches(name))
+    }
+
+    /// The `--member` and `--exclude-member` patterns that match none of `names`
+    pub(crate) fn unmatched<'n>(
+        &self,
+        names: impl Iterator<Item = &'n str> + Clone,
+    ) -> Vec<String> {
+        self.members
+            .iter()
+            .chain(&self.excludes)
+            .filter(|m| !names.clone().any(|name| m.matches(name)))
+            .map(|m| m.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn glob_and_regex_patterns() {
+        let selection = Selection::new(
+            &["aws-sdk-*@1.50".to_owned(), "/^tokio(-util)?$/".to_owned()],
+            &["aws-sdk-s3".to_owned()],
+        )
+        .unwrap();
+        assert!(selection.selects("aws-sdk-ec2"));
+        assert!(!selection.selects("aws-sdk-s3"));
+        assert!(!selection.selects("aws-config"));
+        assert!(selection.selects("tokio-util"));
+        assert!(!selection.selects("tokio-stream"));
+        assert_eq!(selection.version_req("aws-sdk-ec2"), Some("1.50"));
+        assert_eq!(selection.version_req("tokio"), None);
+
+        let names = ["aws-sdk-ec2".to_owned()].into();
+        assert_eq!(selection.unmatched(&names), ["/^tokio(-util)?$/"]);
+    }
+
+    #[test]
+    fn members_by_glob() {
+        let members = MemberSelection::new(&["api*".to_owned()], &["api-docs".to_owned()]).unwrap();
+        assert!(members.selects("api"));
+        assert!(members.selects("api-client"));
+        assert!(!members.selects("api-docs"));
+        assert!(!members.selects("worker"));
+        assert!(
+            members
+                .unmatched(["api", "api-docs", "worker"].into_iter())
+                .is_empty()
+        );
+
+        let mut members = MemberSelection::new(&[], &[]).unwrap();
+        assert!(members.is_all());
+        assert!(members.selects("worker"));
+        members.push("api".to_owned());
+        assert!(!members.selects("worker"));
+        assert_eq!(members.unmatched(["worker"].into_iter()), ["api"]);
+    }
+
+    #[test]
+    fn version_after_regex() {
+        let spec = PackageSpec::parse("/^a@b$/@2").unwrap();
+        assert!(spec.pattern.matches("a@b"));
+        assert_eq!(spec.version_req.as_deref(), Some("2"));
+        assert!(PackageSpec::parse("serde@>>1").is_err());
+        assert!(PackageSpec::parse("/(/").is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! How far the locked registry dependencies are behind their latest releases
+
+use std::collections::BTreeSet;
+
+use anyhow::Context as _;
+use cargo_edit::{
+    CargoResult, DepTable, IndexCache, PrereleasePolicy, PublishTimes, installable_versions,
+    registry_url, shell_write_stdout,
+};
+use cargo_metadata::{DependencyKind, Metadata};
+use jiff::Timestamp;
+use semver::Version;
+use termcolor::ColorSpec;
+
+use crate::advisory::req_minimum;
+use crate::dedupe::compatibility;
+use crate::select::{MemberSelection, Selection};
+use crate::upgrade::write_table;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum StalenessFormat {
+    Table,
+    Json,
+}
+
+/// How far behind a dependency of a member is
+#[derive(serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct DepStaleness {
+    member: String,
+    name: String,
+    /// The table declaring the dependency, like `dev`
+    table: String,
+    req: String,
+    /// The version in `Cargo.lock`, or the minimum of `req` without one
+    locked: Version,
+    latest: Version,
+    /// Releases newer than `locked`, up to `latest`
+    releases_behind: usize,
+    /// Incompatible lines newer than `locked`, like `0.8` and `1` for `0.7.3`
+    majors_behind: usize,
+    /// Days since `locked` was published, when the registry recorded it
+    locked_age_days: Option<i64>,
+    latest_age_days: Option<i64>,
+}
+
+/// The staleness of a member's dependencies, counting each package once
+#[derive(serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct MemberTotals {
+    member: String,
+    dependencies: usize,
+    /// Dependencies with a newer release
+    outdated: usize,
+    releases_behind: usize,
+    majors_behind: usize,
+    /// Days since the oldest locked release was published
+    oldest_locked_age_days: Option<i64>,
+}
+
+/// Staleness of every selected workspace member, for `--staleness`
+#[derive(serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct StalenessReport {
+    /// When the report was made, to track staleness over time
+    generated: Timestamp,
+    members: Vec<MemberTotals>,
+    dependencies: Vec<DepStaleness>,
+}
+
+impl StalenessReport {
+    pub(crate) fn new(now: Timestamp) -> Self {
+        Self {
+            generated: now,
+            members: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    /// Add the registry dependencies of the members of `metadata` that `members` selects
+    pub(crate) fn collect(
+        &mut self,
+        metadata: &Metadata,
+        members: &MemberSelection,
+        selection: &Selection,
+        selects_table: impl Fn(&DepTable) -> bool,
+        prerelease: impl Fn(&str) -> PrereleasePolicy,
+        index: &mut IndexCache,
+    ) -> CargoResult<()> {
+        for member in metadata.workspace_packages() {
+            if !members.selects(&member.name) {
+                continue;
+            }
+            let mut rows = Vec::new();
+            for dep in &member.dependencies {
+                let is_registry = dep.source.as_ref().is_some_and(|s| {
+                    s.repr.starts_with("registry+") || s.repr.starts_with("sparse+")
+                });
+                if !is_registry || !selection.selects(&dep.name) {
+                    continue;
+                }
+                let Some(table) = dep_table(dep) else {
+                    continue;
+                };
+                if !selects_table(&table) {
+                    continue;
+                }
+
+                let registry = match &dep.registry {
+                    Some(registry) => registry
+                        .parse()
+                        .with_context(|| format!("invalid registry `{registry}`"))?,
+                    None => registry_url(member.manifest_path.as_std_path(), None)?,
+                };
+                let Some(krate) = index.krate(&registry, &dep.name)? else {
+                    continue;
+                };
+                let locked = metadata
+                    .packages
+                    .iter()
+                    .filter(|p| p.name.as_str() == dep.name && p.source == dep.source)
+                    .map(|p| &p.version)
+                    .filter(|v| dep.req.matches(v))
+                    .max()
+                    .cloned()
+                    .or_else(|| req_minimum(&dep.req));
+                let Some(locked) = locked else {
+                    continue;
+                };
+                let behind = Behind::new(&krate.versions, &locked, prerelease(&dep.name));
+                let publish_times = index.publish_times(&registry, &dep.name)?;
+                let age = |version: &Version| age_days(&publish_times, version, self.generated);
+                rows.push(DepStaleness {
+                    member: member.name.to_string(),
+                    name: dep.name.clone(),
+                    table: table.to_string(),
+                    req: dep.req.to_string(),
+                    locked_age_days: age(&locked),
+                    latest_age_days: age(&behind.latest),
+                    locked,
+                    latest: behind.latest,
+                    releases_behind: behind.releases,
+                    majors_behind: behind.majors,
+                });
+            }
+
+            let mut counted = BTreeSet::new();
+            let mut totals = MemberTotals {
+                member: member.name.to_string(),
+                dependencies: 0,
+                outdated: 0,
+                releases_behind: 0,
+                majors_behind: 0,
+                oldest_locked_age_days: None,
+            };
+            for row in &rows {
+                if !counted.insert((&row.name, &row.locked)) {
+                    continue;
+                }
+                totals.dependencies += 1;
+                if 0 < row.releases_behind {
+                    totals.outdated += 1;
+                }
+                totals.releases_behind += row.releases_behind;
+                totals.majors_behind += row.majors_behind;
+                totals.oldest_locked_age_days =
+                    totals.oldest_locked_age_days.max(row.locked_age_days);
+            }
+            self.members.push(totals);
+            self.dependencies.extend(rows);
+        }
+        Ok(())
+    }
+
+    /// Write the report to stdout
+    pub(crate) fn write(&self, format: StalenessFormat) -> CargoResult<()> {
+        match format {
+            StalenessFormat::Table => {
+                let rows = self
+                    .dependencies
+                    .iter()
+                    .map(|d| {
+                        [
+                            d.member.clone(),
+                            d.name.clone(),
+                            d.table.clone(),
+                            d.locked.to_string(),
+                            d.latest.to_string(),
+                            d.releases_behind.to_string(),
+                            d.majors_behind.to_string(),
+                            days(d.locked_age_days),
+                            days(d.latest_age_days),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                write_table(
+                    [
+                        "member",
+                        "name",
+                        "table",
+                        "locked",
+                        "latest",
+                        "behind",
+                        "majors",
+                        "locked age",
+                        "latest age",
+                    ],
+                    &rows,
+                    |_| ColorSpec::new(),
+                )?;
+                shell_write_stdout("\n", &ColorSpec::new())?;
+                let rows = self
+                    .members
+                    .iter()
+                    .map(|m| {
+                        [
+                            m.member.clone(),
+                            m.dependencies.to_string(),
+                            m.outdated.to_string(),
+                            m.releases_behind.to_string(),
+                            m.majors_behind.to_string(),
+                            days(m.oldest_locked_age_days),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                write_table(
+                    [
+                        "member",
+                        "dependencies",
+                        "outdated",
+                        "behind",
+                        "majors",
+                        "oldest locked",
+                    ],
+                    &rows,
+                    |_| ColorSpec::new(),
+                )
+            }
+            StalenessFormat::Json => {
+                use std::io::Write as _;
+                let mut json = serde_json::to_string_pretty(self)
+                    .context("failed to serialize staleness report")?;
+                json.push('\n');
+                std::io::stdout()
+                    .write_all(json.as_bytes())
+                    .context("failed to write staleness report")
+            }
+        }
+    }
+}
+
+/// How far behind the latest release a locked version is
+struct Behind {
+    latest: Version,
+    releases: usize,
+    majors: usize,
+}
+
+impl Behind {
+    fn new(
+        versions: &[tame_index::IndexVersion],
+        locked: &Version,
+        prerelease: PrereleasePolicy,
+    ) -> Self {
+        let newer = installable_versions(versions, None)
+            .map(|(_, version)| version)
+            .filter(|version| locked < version && prerelease.allows(&locked.pre, version))
+            .collect::<Vec<_>>();
+        let majors = newer
+            .iter()
+            .map(compatibility)
+            .filter(|range| *range != compatibility(locked))
+            .collect::<BTreeSet<_>>();
+        Self {
+            latest: newer.iter().max().unwrap_or(locked).clone(),
+            releases: newer.len(),
+            majors: majors.len(),
+        }
+    }
+}
+
+/// The table a dependency is declared in, as `cargo metadata` describes it
+fn dep_table(dep: &cargo_metadata::Dependency) -> Option<DepTable> {
+    let kind = match dep.kind {
+        DependencyKind::Normal => "dependencies",
+        DependencyKind::Development => "dev-dependencies",
+        DependencyKind::Build => "build-dependencies",
+        _ => return None,
+    };
+    let path = match &dep.target {
+        Some(target) => vec!["target".to_owned(), target.to_string(), kind.to_owned()],
+        None => vec![kind.to_owned()],
+    };
+    DepTable::from_path(&path)
+}
+
+fn age_days(publish_times: &PublishTimes, version: &Version, now: Timestamp) -> Option<i64> {
+    let published = publish_times
+        .get(&version.to_string())?
+        .parse::<Timestamp>()
+        .ok()?;
+    Some(now.duration_since(published).as_secs() / (24 * 60 * 60))
+}
+
+fn days(days: Option<i64>) -> String {
+    days.map(|d| format!("{d}d"))
+        .unwrap_or_else(|| "-".to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn releases_and_majors_behind() {
+        let mut versions = ["0.7.3", "0.7.4", "0.8.0", "0.8.1", "1.0.0-rc.1", "1.0.0"]
+            .map(|v| tame_index::IndexVersion::fake("toml", v))
+            .to_vec();
+        versions[3].yanked = true;
+        let behind = Behind::new(
+            &versions,
+            &"0.7.3".parse().unwrap(),
+            PrereleasePolicy::Never,
+        );
+        assert_eq!(behind.latest, Version::new(1, 0, 0));
+        assert_eq!(behind.releases, 3);
+        assert_eq!(behind.majors, 2);
+
+        let behind = Behind::new(
+            &versions,
+            &"1.0.0".parse().unwrap(),
+            PrereleasePolicy::Never,
+        );
+        assert_eq!(behind.latest, Version::new(1, 0, 0));
+        assert_eq!(behind.releases, 0);
+        assert_eq!(behind.majors, 0);
+    }
+
+    #[test]
+    fn age_from_publish_time() {
+        let publish_times =
+            PublishTimes::from([("1.0.0".to_owned(), "2026-01-01T12:00:00Z".to_owned())]);
+        let now = "2026-01-11T13:00:00Z".parse().unwrap();
+        assert_eq!(
+            age_days(&publish_times, &Version::new(1, 0, 0), now),
+            Some(10)
+        );
+        assert_eq!(age_days(&publish_times, &Version::new(1, 0, 1), now), None);
+    }
+}
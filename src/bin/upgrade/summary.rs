@@ -0,0 +1,300 @@
+//! Upgrade summaries for other tools, like pull request descriptions
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::Context as _;
+use cargo_edit::CargoResult;
+
+use crate::lockfile::LockChange;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum SummaryFormat {
+    Markdown,
+    Json,
+}
+
+/// How to get more upgrades on a re-run
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Hint {
+    Pinned,
+    Incompatible,
+    RustVersion,
+}
+
+impl Hint {
+    pub(crate) fn message(&self) -> &'static str {
+        match self {
+            Self::Pinned => "Re-run with `--pinned` to upgrade pinned version requirements",
+            Self::Incompatible => {
+                "Re-run with `--incompatible` to upgrade incompatible version requirements"
+            }
+            Self::RustVersion => {
+                "Re-run with `--ignore-rust-version` to upgrade to versions requiring a newer Rust"
+            }
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Pinned => "pinned",
+            Self::Incompatible => "incompatible",
+            Self::RustVersion => "rust-version",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Section {
+    Compatible,
+    Incompatible,
+    HeldBack,
+}
+
+impl Section {
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Compatible => "Compatible upgrades",
+            Self::Incompatible => "Incompatible upgrades",
+            Self::HeldBack => "Held back",
+        }
+    }
+}
+
+/// A dependency as reported in the summary
+#[derive(serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct SummaryRow {
+    pub(crate) member: String,
+    /// Package name of the dependency
+    pub(crate) package: String,
+    /// Name as displayed, including renames and patch tables
+    pub(crate) name: String,
+    /// The table declaring the dependency, like `dev` or `workspace`
+    pub(crate) table: String,
+    /// The crate's registry page
+    pub(crate) link: Option<String>,
+    pub(crate) old_req: String,
+    /// Versions in `Cargo.lock` before upgrading
+    pub(crate) locked: Option<String>,
+    pub(crate) new_req: String,
+    pub(crate) latest: Option<String>,
+    pub(crate) reason: &'static str,
+    /// Why a `# cargo-upgrade:` annotation holds the dependency back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) note: Option<String>,
+    pub(crate) section: Section,
+    pub(crate) hints: Vec<Hint>,
+}
+
+#[derive(Default, serde_derive::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Summary {
+    #[serde(rename = "dependencies")]
+    rows: Vec<SummaryRow>,
+    /// Changes to `Cargo.lock`, once it was refreshed
+    lockfile: Vec<LockChange>,
+}
+
+impl Summary {
+    pub(crate) fn push(&mut self, row: SummaryRow) {
+        self.rows.push(row);
+    }
+
+    pub(crate) fn add_lockfile_changes(&mut self, changes: Vec<LockChange>) {
+        self.lockfile.extend(changes);
+    }
+
+    /// Write the summary to `path`, or stdout without one
+    pub(crate) fn write(&self, format: SummaryFormat, path: Option<&Path>) -> CargoResult<()> {
+        let content = match format {
+            SummaryFormat::Markdown => self.to_markdown(),
+            SummaryFormat::Json => {
+                let mut json =
+                    serde_json::to_string_pretty(self).context("failed to serialize summary")?;
+                json.push('\n');
+                json
+            }
+        };
+        match path {
+            Some(path) => std::fs::write(path, content)
+                .with_context(|| format!("failed to write summary {}", path.display())),
+            None => {
+                use std::io::Write as _;
+                std::io::stdout()
+                    .write_all(content.as_bytes())
+                    .context("failed to write summary")
+            }
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        for section in [
+            Section::Compatible,
+            Section::Incompatible,
+            Section::HeldBack,
+        ] {
+            let rows = self
+                .rows
+                .iter()
+                .filter(|r| r.section == section)
+                .collect::<Vec<_>>();
+            if rows.is_empty() {
+                continue;
+            }
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let _ = writeln!(out, "### {}\n", section.title());
+            if section == Section::HeldBack {
+                out.push_str("| Crate | Member | Req | Latest | Reason |\n");
+                out.push_str("|-------|--------|-----|--------|--------|\n");
+            } else {
+                out.push_str("| Crate | Member | Old req | New req | Latest |\n");
+                out.push_str("|-------|--------|---------|---------|--------|\n");
+            }
+            for row in rows {
+                let name = match &row.link {
+                    Some(link) => format!("[{}]({link})", escape(&row.name)),
+                    None => escape(&row.name),
+                };
+                let latest = escape(row.latest.as_deref().unwrap_or("-"));
+                let footnotes = row
+                    .hints
+                    .iter()
+                    .map(|h| format!("[^{}]", h.label()))
+                    .collect::<String>();
+                if section == Section::HeldBack {
+                    let reason = match &row.note {
+                        Some(note) => format!("{}: {}", row.reason, escape(note)),
+                        None => row.reason.to_owned(),
+                    };
+                    let _ = writeln!(
+                        out,
+                        "| {name} | {} | `{}` | {latest} | {reason}{footnotes} |",
+                        escape(&row.member),
+                        row.old_req,
+                    );
+                } else {
+                    let _ = writeln!(
+                        out,
+                        "| {name} | {} | `{}` | `{}` | {latest}{footnotes} |",
+                        escape(&row.member),
+                        row.old_req,
+                        row.new_req,
+                    );
+                }
+            }
+        }
+        if out.is_empty() {
+            out.push_str("No dependencies to upgrade.\n");
+        }
+
+        if !self.lockfile.is_empty() {
+            out.push_str("\n### Lockfile changes\n\n");
+            out.push_str("| Crate | Dependency | Old | New | Change |\n");
+            out.push_str("|-------|------------|-----|-----|--------|\n");
+            for change in &self.lockfile {
+                let version = |v: &Option<semver::Version>| {
+                    v.as_ref()
+                        .map(|v| v.to_string())
+                        .unwrap_or_else(|| "-".to_owned())
+                };
+                let change_text = if change.source_changed() {
+                    format!(
+                        "**{}** (`{}` to `{}`)",
+                        change.change(),
+                        change.old_source.as_deref().unwrap_or("path"),
+                        change.new_source.as_deref().unwrap_or("path")
+                    )
+                } else {
+                    change.change()
+                };
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} |",
+                    escape(&change.name),
+                    change.dependency(),
+                    version(&change.old_version),
+                    version(&change.new_version),
+                    change_text,
+                );
+            }
+        }
+
+        let mut hints = self
+            .rows
+            .iter()
+            .flat_map(|r| r.hints.iter().copied())
+            .collect::<Vec<_>>();
+        hints.sort();
+        hints.dedup();
+        if !hints.is_empty() {
+            out.push('\n');
+            for hint in hints {
+                let _ = writeln!(out, "[^{}]: {}", hint.label(), hint.message());
+            }
+        }
+        out
+    }
+}
+
+/// Escape text for a table cell
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn row(name: &str, section: Section, hints: Vec<Hint>) -> SummaryRow {
+        SummaryRow {
+            member: "foo".to_owned(),
+            package: name.to_owned(),
+            name: name.to_owned(),
+            table: "normal".to_owned(),
+            link: Some(format!("https://crates.io/crates/{name}")),
+            old_req: "1.0".to_owned(),
+            locked: Some("1.0.5".to_owned()),
+            new_req: "1.2".to_owned(),
+            latest: Some("2.0.0".to_owned()),
+            reason: "incompatible",
+            note: None,
+            section,
+            hints,
+        }
+    }
+
+    #[test]
+    fn markdown_sections_and_footnotes() {
+        let mut summary = Summary::default();
+        summary.push(row("serde", Section::Compatible, vec![]));
+        summary.push(row("clap", Section::HeldBack, vec![Hint::Incompatible]));
+        let expected = "\
+### Compatible upgrades
+
+| Crate | Member | Old req | New req | Latest |
+|-------|--------|---------|---------|--------|
+| [serde](https://crates.io/crates/serde) | foo | `1.0` | `1.2` | 2.0.0 |
+
+### Held back
+
+| Crate | Member | Req | Latest | Reason |
+|-------|--------|-----|--------|--------|
+| [clap](https://crates.io/crates/clap) | foo | `1.0` | 2.0.0 | incompatible[^incompatible] |
+
+[^incompatible]: Re-run with `--incompatible` to upgrade incompatible version requirements
+";
+        assert_eq!(summary.to_markdown(), expected);
+    }
+
+    #[test]
+    fn markdown_without_rows() {
+        let summary = Summary::default();
+        assert_eq!(summary.to_markdown(), "No dependencies to upgrade.\n");
+    }
+}
@@ -5,15 +5,33 @@ use std::path::PathBuf;
 
 use anyhow::Context as _;
 use cargo_edit::{
-    CargoResult, CertsSource, CrateSpec, Dependency, IndexCache, LocalManifest, RustVersion,
-    Source, find_compatible_version, find_latest_version, registry_url, set_dep_version,
-    shell_note, shell_status, shell_warn, shell_write_stdout,
+    CargoResult, CertsSource, DepKind, DepTable, Dependency, EditJournal, IndexCache,
+    LocalManifest, ManifestTransaction, PrereleasePolicy, RustVersion, Source,
+    find_compatible_version, find_latest_version, installable_versions, is_script, registry_url,
+    root_manifest_path, set_dep_version, shell_note, shell_status, shell_warn, shell_write_stdout,
+    undo_last_run,
 };
+use cargo_metadata::cargo_platform::{Cfg, Platform};
 use clap::Args;
-use indexmap::IndexMap;
+use jiff::Timestamp;
 use semver::{Op, VersionReq};
 use termcolor::{Color, ColorSpec};
 
+use crate::advisory::{AdvisoryDb, Vulnerability, req_minimum};
+use crate::annotation::{Annotation, Directive};
+use crate::coherent::{self, Candidate};
+use crate::commit;
+use crate::dedupe::{self, Release, compatibility};
+use crate::discover;
+use crate::explain::{Explanation, Trace};
+use crate::lockfile;
+use crate::patch;
+use crate::plan::{self, IndexSnapshot, PlannedEdit, UpgradePlan};
+use crate::select::{MemberSelection, Selection};
+use crate::staleness::{StalenessFormat, StalenessReport};
+use crate::summary::{Hint, Section, Summary, SummaryFormat, SummaryRow};
+use crate::verify;
+
 /// Upgrade dependency version requirements in Cargo.toml manifest files
 #[derive(Debug, Args)]
 #[command(version)]
@@ -22,10 +40,86 @@ pub(crate) struct UpgradeArgs {
     #[arg(long, short = 'n')]
     dry_run: bool,
 
+    /// Print a unified diff of the changes to each manifest
+    #[arg(long)]
+    diff: bool,
+
+    /// Summarize the upgrades as GitHub-flavored `markdown` or `json`, in place of the table on
+    /// stdout
+    #[arg(long, value_name = "FORMAT", value_enum)]
+    summary: Option<SummaryFormat>,
+
+    /// Write the summary to this file instead of stdout
+    #[arg(long, value_name = "PATH", requires = "summary")]
+    summary_out: Option<PathBuf>,
+
+    /// Explain, release by release, why this dependency is upgraded as it is, without upgrading
+    /// anything
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["undo", "apply", "dedupe", "verify", "commit_each", "plan_out"]
+    )]
+    explain: Option<String>,
+
+    /// Report how far each registry dependency is behind its latest release, as a `table` or
+    /// `json` with totals per member, without upgrading anything
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        num_args = 0..=1,
+        default_missing_value = "table",
+        conflicts_with_all = ["undo", "apply", "dedupe", "explain", "verify", "commit_each", "plan_out", "summary", "bump_rust_version"]
+    )]
+    staleness: Option<StalenessFormat>,
+
+    /// Restore the manifests and lockfile changed by the last run
+    #[arg(
+        long,
+        conflicts_with_all = ["dry_run", "package", "exclude", "plan_out", "apply"]
+    )]
+    undo: bool,
+
+    /// Apply upgrades one dependency at a time, reverting those that make this command fail
+    #[arg(
+        long,
+        value_name = "CMD",
+        conflicts_with_all = ["dry_run", "locked", "plan_out", "apply"]
+    )]
+    verify: Option<String>,
+
+    /// Create a git commit for each upgraded dependency, along with its `Cargo.lock` changes
+    #[arg(
+        long,
+        conflicts_with_all = ["dry_run", "locked", "plan_out", "apply", "verify", "undo"]
+    )]
+    commit_each: bool,
+
     /// Path to the manifest to upgrade
     #[arg(long, value_name = "PATH")]
     manifest_path: Option<PathBuf>,
 
+    /// Upgrade every workspace with a manifest under this directory, skipping ignored files
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["manifest_path", "current_member", "undo", "apply", "plan_out", "dedupe"]
+    )]
+    recursive_dirs: Option<PathBuf>,
+
+    /// Only upgrade the manifests of these workspace members, by name or glob
+    #[arg(long, value_name = "NAME", help_heading = "Workspace")]
+    member: Vec<String>,
+
+    /// Workspace members whose manifests to leave untouched, by name or glob
+    #[arg(long, value_name = "NAME", help_heading = "Workspace")]
+    exclude_member: Vec<String>,
+
+    /// Only upgrade the manifest of the member in the current directory
+    #[arg(long, conflicts_with = "member", help_heading = "Workspace")]
+    current_member: bool,
+
     /// Override `rust-version`
     #[arg(long, value_name = "VER", conflicts_with = "ignore_rust_version")]
     rust_version: Option<RustVersion>,
@@ -34,6 +128,13 @@ pub(crate) struct UpgradeArgs {
     #[arg(long)]
     ignore_rust_version: bool,
 
+    /// Upgrade past `rust-version`, raising it to what the upgraded dependencies require
+    #[arg(
+        long,
+        conflicts_with_all = ["rust_version", "ignore_rust_version", "apply", "plan_out", "verify", "commit_each"]
+    )]
+    bump_rust_version: bool,
+
     /// Require `Cargo.toml` to be up to date
     #[arg(long)]
     locked: bool,
@@ -85,7 +186,22 @@ pub(crate) struct UpgradeArgs {
     )]
     pinned: Status,
 
-    /// Crate to be upgraded
+    /// Hold back upgrades that other direct dependencies don't accept yet, going by the
+    /// requirements of each release in the index
+    #[arg(long, help_heading = "Version")]
+    coherent: bool,
+
+    /// Pre-releases to upgrade to: `never`, `same-channel` (default) or `any`, optionally for one
+    /// crate
+    #[arg(
+        long,
+        value_name = "[CRATE=]POLICY",
+        value_parser = parse_prerelease,
+        help_heading = "Version"
+    )]
+    prerelease: Vec<PrereleaseArg>,
+
+    /// Crate to be upgraded, or a glob like `aws-sdk-*` or `/regex/` selecting several
     #[arg(
         long,
         short,
@@ -94,10 +210,23 @@ pub(crate) struct UpgradeArgs {
     )]
     package: Vec<String>,
 
-    /// Crates to exclude and not upgrade.
+    /// Crates to exclude and not upgrade, by name, glob or `/regex/`
     #[arg(long, value_name = "PKGID", help_heading = "Dependencies")]
     exclude: Vec<String>,
 
+    /// Only upgrade dependencies of this kind: `normal`, `dev` or `build`
+    #[arg(long, value_name = "KIND", help_heading = "Dependencies")]
+    kind: Vec<DepKind>,
+
+    /// Only upgrade dependencies in `[target]` tables applying to a triple, or in the
+    /// `[target.<CFG>]` table, like `cfg(windows)`
+    #[arg(long, value_name = "TRIPLE-OR-CFG", help_heading = "Dependencies")]
+    target: Option<String>,
+
+    /// The cfg of the `--target` triple, to match `[target.'cfg(..)']` tables against
+    #[arg(skip)]
+    target_cfg: Option<Vec<Cfg>>,
+
     /// Recursively update locked dependencies
     #[arg(
         long,
@@ -109,6 +238,38 @@ pub(crate) struct UpgradeArgs {
         help_heading = "Dependencies"
     )]
     recursive: Option<bool>,
+
+    /// Upgrade dependencies affected by an advisory in this local `rustsec/advisory-db`
+    /// checkout, even when incompatible
+    #[arg(long, value_name = "PATH", help_heading = "Dependencies")]
+    advisory_db: Option<PathBuf>,
+
+    /// Suggest upgrades that remove crates locked in several incompatible versions, without
+    /// upgrading anything
+    #[arg(
+        long,
+        conflicts_with_all = ["plan_out", "apply", "verify", "commit_each", "undo"],
+        help_heading = "Dependencies"
+    )]
+    dedupe: bool,
+
+    /// Record the upgrades to a plan file instead of applying them
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with = "dry_run",
+        help_heading = "Plan"
+    )]
+    plan_out: Option<PathBuf>,
+
+    /// Apply the upgrades recorded in a plan file, without accessing the network
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["plan_out", "package", "exclude", "kind", "target", "member", "exclude_member", "current_member", "incompatible", "pinned", "coherent", "recursive", "advisory_db"],
+        help_heading = "Plan"
+    )]
+    apply: Option<PathBuf>,
 }
 
 impl UpgradeArgs {
@@ -127,6 +288,48 @@ impl UpgradeArgs {
         }
     }
 
+    /// The `--prerelease` policy for `name`, preferring crate-specific ones
+    fn prerelease_policy(&self, name: &str) -> PrereleasePolicy {
+        let specific = self
+            .prerelease
+            .iter()
+            .rev()
+            .find(|p| p.krate.as_deref() == Some(name));
+        let general = || self.prerelease.iter().rev().find(|p| p.krate.is_none());
+        specific
+            .or_else(general)
+            .map(|p| p.policy)
+            .unwrap_or_default()
+    }
+
+    /// Whether `--kind` or `--target` limit the tables to upgrade
+    fn filters_tables(&self) -> bool {
+        !self.kind.is_empty() || self.target.is_some()
+    }
+
+    /// Whether `--kind` and `--target` select the dependencies in `table`
+    fn selects_table(&self, table: &DepTable) -> bool {
+        let kind = self.kind.is_empty() || self.kind.contains(&table.kind());
+        let target = match &self.target {
+            Some(target) => table
+                .target()
+                .is_some_and(|t| self.selects_target(t, target)),
+            None => true,
+        };
+        kind && target
+    }
+
+    /// Whether the `[target.<key>]` table applies to `--target`
+    fn selects_target(&self, key: &str, target: &str) -> bool {
+        if strip_whitespace(key) == strip_whitespace(target) {
+            return true;
+        }
+        match (&self.target_cfg, key.parse::<Platform>()) {
+            (Some(target_cfg), Ok(Platform::Cfg(expr))) => expr.matches(target_cfg),
+            _ => false,
+        }
+    }
+
     fn is_verbose(&self) -> bool {
         0 < self.verbose_num()
     }
@@ -160,28 +363,287 @@ impl Status {
     }
 }
 
+/// A `--prerelease` policy, for every dependency or only `krate`
+#[derive(Clone, Debug)]
+struct PrereleaseArg {
+    krate: Option<String>,
+    policy: PrereleasePolicy,
+}
+
+fn parse_prerelease(text: &str) -> CargoResult<PrereleaseArg> {
+    let (krate, policy) = match text.split_once('=') {
+        Some((krate, policy)) => (Some(krate.to_owned()), policy),
+        None => (None, text),
+    };
+    Ok(PrereleaseArg {
+        krate,
+        policy: policy.parse()?,
+    })
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
 enum UnstableOptions {}
 
 /// Main processing function. Allows us to return a `Result` so that `main` can print pretty error
 /// messages.
-fn exec(args: UpgradeArgs) -> CargoResult<()> {
+fn exec(mut args: UpgradeArgs) -> CargoResult<()> {
     env_logger::Builder::from_env("CARGO_LOG")
         .filter_level(args.verbose.log_level_filter())
         .init();
 
+    if let Some(target) = &args.target
+        && !matches!(target.parse::<Platform>(), Ok(Platform::Cfg(_)))
+    {
+        args.target_cfg = Some(target_cfg(target)?);
+    }
+
     let offline = false;
     let mut index = IndexCache::new(CertsSource::Native);
 
-    let metadata = resolve_ws(args.manifest_path.as_deref(), args.locked, offline)?;
-    let root_manifest_path = metadata.workspace_root.as_std_path().join("Cargo.toml");
-    let manifests = find_ws_members(&metadata);
+    if args.undo {
+        return undo_last_run(args.manifest_path.as_deref());
+    }
+
+    if let Some(plan_path) = &args.apply {
+        let offline = true;
+        let metadata = resolve_ws(args.manifest_path.as_deref(), args.locked, offline)?;
+        let root_manifest_path = root_manifest_path(&metadata, args.manifest_path.as_deref());
+        plan::apply(
+            plan_path,
+            &metadata,
+            &root_manifest_path,
+            args.dry_run,
+            args.diff,
+            args.locked,
+        )?;
+        if args.dry_run {
+            shell_warn("aborting upgrade due to dry run")?;
+        }
+        return Ok(());
+    }
+
+    if args.dedupe {
+        let metadata = resolve_ws(args.manifest_path.as_deref(), args.locked, offline)?;
+        let root_manifest_path = root_manifest_path(&metadata, args.manifest_path.as_deref());
+        return dedupe::suggest(
+            &metadata,
+            &root_manifest_path,
+            &mut index,
+            args.rust_version,
+        );
+    }
+
+    let workspaces = match &args.recursive_dirs {
+        Some(dir) => discover::workspaces(dir, args.locked, offline)?,
+        None => vec![(
+            args.manifest_path.clone(),
+            resolve_ws(args.manifest_path.as_deref(), args.locked, offline)?,
+        )],
+    };
+
+    let mut member_selection = MemberSelection::new(&args.member, &args.exclude_member)?;
+    if args.current_member {
+        let (_, metadata) = &workspaces[0];
+        member_selection.push(current_member(&find_ws_members(metadata))?);
+    }
+    let member_names = workspaces
+        .iter()
+        .flat_map(|(_, metadata)| find_ws_members(metadata))
+        .map(|p| p.name.to_string())
+        .collect::<Vec<_>>();
+    let unknown_members = member_selection.unmatched(member_names.iter().map(|n| n.as_str()));
+    match unknown_members.len() {
+        0 => {}
+        1 => anyhow::bail!("member {} doesn't exist", unknown_members.join(", ")),
+        _ => anyhow::bail!("members {} don't exist", unknown_members.join(", ")),
+    }
+    let selection = Selection::new(&args.package, &args.exclude)?;
+
+    if let Some(format) = args.staleness {
+        let mut staleness = StalenessReport::new(Timestamp::now());
+        for (_, metadata) in &workspaces {
+            staleness.collect(
+                metadata,
+                &member_selection,
+                &selection,
+                |table| args.selects_table(table),
+                |name| args.prerelease_policy(name),
+                &mut index,
+            )?;
+        }
+        return staleness.write(format);
+    }
+
+    let mut report = Report {
+        summary: args.summary.map(|_| Summary::default()),
+        ..Default::default()
+    };
+    for (manifest_path, metadata) in &workspaces {
+        if args.recursive_dirs.is_some() {
+            shell_status(
+                "Upgrading",
+                &format!("workspace at {}", metadata.workspace_root),
+            )?;
+        }
+        upgrade_workspace(
+            &args,
+            manifest_path.as_deref(),
+            metadata,
+            &member_selection,
+            &selection,
+            &mut index,
+            &mut report,
+        )?;
+    }
+
+    let unused = selection.unmatched(&report.processed_keys);
+    match unused.len() {
+        0 => {}
+        1 => anyhow::bail!("dependency {} doesn't exist", unused.join(", ")),
+        _ => anyhow::bail!("dependencies {} don't exist", unused.join(", ")),
+    }
+    if let Some(name) = &args.explain
+        && !report.processed_keys.contains(name)
+    {
+        anyhow::bail!("dependency {name} doesn't exist");
+    }
+
+    if report.pinned_present {
+        shell_note(Hint::Pinned.message())?;
+    }
+    if report.incompatible_present {
+        shell_note(Hint::Incompatible.message())?;
+    }
+    if report.rust_version_present {
+        shell_note(Hint::RustVersion.message())?;
+    }
+
+    if !report.uninteresting_crates.is_empty() {
+        let mut categorize = BTreeMap::new();
+        for dep in report.uninteresting_crates {
+            categorize
+                .entry(dep.long_reason())
+                .or_insert_with(BTreeSet::new)
+                .insert(dep.name);
+        }
+        let verbose_flags = if args.is_verbose() {
+            "`--verbose --verbose`"
+        } else {
+            "`--verbose`"
+        };
+        let mut note = format!("Re-run with {verbose_flags} to show more dependencies");
+        for (reason, deps) in categorize {
+            use std::fmt::Write;
+            write!(&mut note, "\n  {reason}: ")?;
+            if deps.len() <= 3 {
+                for (i, dep) in deps.into_iter().enumerate() {
+                    if 0 < i {
+                        note.push_str(", ");
+                    }
+                    note.push_str(&dep);
+                }
+            } else {
+                let number = deps.len();
+                let plural = if number == 1 { "" } else { "s" };
+                write!(&mut note, "{number} package{plural}")?;
+            }
+        }
+        shell_note(&note)?;
+    }
+
+    if let (Some(format), Some(summary)) = (args.summary, &report.summary) {
+        summary.write(format, args.summary_out.as_deref())?;
+    }
+
+    if args.dry_run || args.explain.is_some() {
+        shell_warn("aborting upgrade due to dry run")?;
+    }
+
+    Ok(())
+}
+
+/// What is reported once all workspaces are upgraded
+#[derive(Default)]
+struct Report {
+    processed_keys: BTreeSet<String>,
+    pinned_present: bool,
+    incompatible_present: bool,
+    rust_version_present: bool,
+    uninteresting_crates: BTreeSet<Dep>,
+    summary: Option<Summary>,
+}
+
+/// The upgrade table of one manifest, with what else is reported alongside it
+struct MemberTable {
+    member: String,
+    deps: Vec<Dep>,
+    explanations: Vec<Explanation>,
+    advisory_warnings: Vec<String>,
+}
+
+/// Report the table of one manifest, holding back the packages `--verify` rolled back
+fn report_member_table(
+    args: &UpgradeArgs,
+    report: &mut Report,
+    member_table: MemberTable,
+    reverted: &BTreeSet<String>,
+) -> CargoResult<()> {
+    let MemberTable {
+        member,
+        mut deps,
+        explanations,
+        advisory_warnings,
+    } = member_table;
+    for dep in &mut deps {
+        if dep.req_changed() && reverted.contains(dep.package()) {
+            dep.new_version_req = dep.old_version_req.clone();
+            dep.reason = Some(Reason::FailedVerification);
+        }
+    }
+    if let Some(summary) = &mut report.summary {
+        for dep in &deps {
+            if let Some(row) = dep.summary_row(&member) {
+                summary.push(row);
+            }
+        }
+    }
+    if !deps.is_empty() {
+        let (interesting, uninteresting) = deps
+            .into_iter()
+            .partition::<Vec<_>, _>(|d| d.show_for(args.verbose_num()));
+        if report.summary.is_none() || args.summary_out.is_some() {
+            print_upgrade(interesting)?;
+        }
+        report.uninteresting_crates.extend(uninteresting);
+    }
+    for explanation in explanations {
+        explanation.print()?;
+    }
+    for warning in advisory_warnings {
+        shell_warn(&warning)?;
+    }
+    Ok(())
+}
+
+/// Upgrade the workspace of `metadata`, found from `manifest_path`
+fn upgrade_workspace(
+    args: &UpgradeArgs,
+    manifest_path: Option<&Path>,
+    metadata: &cargo_metadata::Metadata,
+    member_selection: &MemberSelection,
+    selection: &Selection,
+    index: &mut IndexCache,
+    report: &mut Report,
+) -> CargoResult<()> {
+    let offline = false;
+    let root_manifest_path = root_manifest_path(metadata, manifest_path);
+    let manifests = find_ws_members(metadata);
     let mut manifests = manifests
         .into_iter()
         .map(|p| {
             let rust_version = if args.rust_version.is_some() {
                 args.rust_version
-            } else if args.ignore_rust_version {
+            } else if args.ignore_rust_version || args.bump_rust_version {
                 None
             } else {
                 p.rust_version.as_ref().map(RustVersion::from)
@@ -209,32 +671,133 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
             ),
         );
     }
+    // Members show the `[workspace.dependencies]` entries they inherit, so check those first
+    manifests.sort_by_key(|(_, path, _)| *path != root_manifest_path);
 
-    let selected_dependencies = args
-        .package
-        .iter()
-        .map(|name| {
-            let spec = CrateSpec::resolve(name)?;
-            Ok((spec.name, spec.version_req))
-        })
-        .collect::<CargoResult<IndexMap<_, Option<_>>>>()?;
-    let mut processed_keys = BTreeSet::new();
+    // Patches apply to the whole workspace, so only when upgrading all of it
+    let upgrade_patches = !args.filters_tables() && member_selection.is_all();
+
+    // `[workspace.dependencies]` entries follow the members and tables inheriting them
+    let inherited_keys = if upgrade_patches {
+        None
+    } else {
+        Some(inherited_keys(
+            args,
+            manifests
+                .iter()
+                .filter(|(name, _, _)| member_selection.selects(name))
+                .map(|(_, p, _)| p),
+        )?)
+    };
+
+    let holds = if args.coherent {
+        let candidates = coherent_candidates(
+            args,
+            metadata,
+            &manifests,
+            member_selection,
+            selection,
+            inherited_keys.as_ref(),
+            index,
+        )?;
+        coherent::plan(&candidates)
+    } else {
+        BTreeMap::new()
+    };
+    for (name, hold) in &holds {
+        shell_warn(&format!(
+            "holding back {name} at {} instead of {}, {}",
+            hold.version,
+            hold.latest,
+            hold.blockers.join(", ")
+        ))?;
+    }
+    let mut advisory_db = args
+        .advisory_db
+        .as_deref()
+        .map(AdvisoryDb::open)
+        .transpose()?;
 
     let mut modified_crates = BTreeSet::new();
     let mut git_crates = BTreeSet::new();
-    let mut pinned_present = false;
-    let mut incompatible_present = false;
-    let mut uninteresting_crates = BTreeSet::new();
+    // Rows of `[workspace.dependencies]`, by key
+    let mut workspace_deps: BTreeMap<String, Dep> = BTreeMap::new();
+    // The `rust-version` required by upgraded `[workspace.dependencies]`, by key
+    let mut workspace_rust_versions = BTreeMap::new();
+    // The workspace `rust-version` to raise to, and the members requiring it
+    let mut workspace_rust_version: Option<(RustVersion, Vec<String>)> = None;
+    let mut upgrade_plan = UpgradePlan::new();
+    let mut transaction = ManifestTransaction::new();
+    // Reported once `--verify` settled which upgrades are kept
+    let mut member_tables = Vec::new();
     for (pkg_name, manifest_path, rust_version) in manifests {
+        // Patches only take effect in the workspace root
+        let is_root = manifest_path == root_manifest_path;
+        let member_selected = member_selection.selects(&pkg_name);
+        if !member_selected && !is_root {
+            continue;
+        }
         let mut manifest = LocalManifest::try_new(&manifest_path)?;
         let mut crate_modified = false;
         let mut table = Vec::new();
+        let mut explanations = Vec::new();
+        let mut advisory_warnings = Vec::new();
+        // The highest `rust-version` the upgrades require, and the dependency requiring it
+        let mut required_rust_version: Option<(RustVersion, String)> = None;
         shell_status("Checking", &format!("{pkg_name}'s dependencies"))?;
-        for dep_table in manifest.get_dependency_tables_mut() {
+        let multi_version = multi_version_packages(&mut manifest, &manifest_path)?;
+        let mut table_paths = manifest.get_dependency_table_paths();
+        if !member_selected {
+            // The root of the workspace still holds `[workspace.dependencies]`
+            table_paths.retain(|p| p[0] == "workspace");
+        }
+        if args.filters_tables() {
+            table_paths.retain(|p| match DepTable::from_path(p) {
+                Some(dep_table) => args.selects_table(&dep_table),
+                None => true,
+            });
+        }
+        // Members, the root package included, inherit from `[workspace.dependencies]`, so its
+        // rows must be known before theirs
+        table_paths.sort_by_key(|p| p[0] != "workspace");
+        if is_root && upgrade_patches {
+            table_paths.extend(
+                manifest
+                    .get_patch_table_paths()
+                    .into_iter()
+                    .filter(|p| p[0] == "patch"),
+            );
+        }
+        for table_path in table_paths {
+            let table_label = table_label(&table_path);
+            let is_workspace_table = table_path[0] == "workspace";
+            let dep_table = manifest
+                .get_table_mut(&table_path)?
+                .as_table_like_mut()
+                .expect("dependency table paths always point to tables");
             for (dep_key, dep_item) in dep_table.iter_mut() {
                 let mut reason = None;
 
+                let annotation =
+                    Annotation::find(dep_key.leaf_decor(), dep_item).with_context(|| {
+                        format!(
+                            "invalid annotation on `{}` in {}",
+                            dep_key.get(),
+                            manifest_path.display()
+                        )
+                    })?;
                 let dep_key = dep_key.get();
+                if table_path[0] == "patch" && patch::is_tag_pin(dep_item) {
+                    // Upgraded by tag below
+                    continue;
+                }
+                if is_workspace_table
+                    && inherited_keys
+                        .as_ref()
+                        .is_some_and(|keys| !keys.contains(dep_key))
+                {
+                    continue;
+                }
                 let dependency = match Dependency::from_toml(&manifest_path, dep_key, dep_item) {
                     Ok(dependency) => dependency,
                     Err(err) => {
@@ -242,15 +805,20 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                         continue;
                     }
                 };
-                processed_keys.insert(dependency.name.clone());
-                if !selected_dependencies.is_empty()
-                    && !selected_dependencies.contains_key(&dependency.name)
-                {
-                    reason.get_or_insert(Reason::Excluded);
-                }
-                if args.exclude.contains(&dependency.name) {
+                report.processed_keys.insert(dependency.name.clone());
+                if !selection.selects(&dependency.name) {
                     reason.get_or_insert(Reason::Excluded);
                 }
+                let max = match annotation.as_ref().map(|a| &a.directive) {
+                    Some(Directive::Hold) => {
+                        reason.get_or_insert(Reason::Held);
+                        None
+                    }
+                    Some(Directive::Max(max)) => Some(max),
+                    None => None,
+                };
+                let note = annotation.as_ref().and_then(|a| a.reason.clone());
+                let explaining = args.explain.as_ref() == Some(&dependency.name);
                 let old_version_req = match dependency.version() {
                     Some(version_req) => version_req.to_owned(),
                     None => {
@@ -262,10 +830,53 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                                 Some(Reason::GitSource)
                             }
                             Some(Source::Path(_)) => Some(Reason::PathSource),
-                            Some(Source::Workspace(_)) | Some(Source::Registry(_)) | None => None,
+                            Some(Source::Workspace(_)) => {
+                                if let Some(inherited) = workspace_deps.get(dep_key) {
+                                    // Upgraded through the workspace entry
+                                    if let Some(required) = workspace_rust_versions.get(dep_key) {
+                                        raise_rust_version(
+                                            &mut required_rust_version,
+                                            *required,
+                                            &dependency.name,
+                                        );
+                                    }
+                                    if explaining {
+                                        explanations.push(Explanation {
+                                            name: dependency.name.clone(),
+                                            member: pkg_name.clone(),
+                                            table: format!("{table_label}, inherited"),
+                                            old_req: inherited.old_version_req.clone(),
+                                            new_req: inherited.new_version_req.clone(),
+                                            reason: inherited.reason,
+                                            releases: Vec::new(),
+                                            inherited: true,
+                                        });
+                                    }
+                                    table.push(Dep {
+                                        table: format!("{table_label}, inherited"),
+                                        inherited: true,
+                                        ..inherited.clone()
+                                    });
+                                    continue;
+                                }
+                                None
+                            }
+                            Some(Source::Registry(_)) | None => None,
                         };
                         if let Some(maybe_reason) = maybe_reason {
                             reason.get_or_insert(maybe_reason);
+                            if explaining {
+                                explanations.push(Explanation {
+                                    name: dependency.name.clone(),
+                                    member: pkg_name.clone(),
+                                    table: table_label.clone(),
+                                    old_req: None,
+                                    new_req: None,
+                                    reason,
+                                    releases: Vec::new(),
+                                    inherited: false,
+                                });
+                            }
                             let display_name = if let Some(rename) = &dependency.rename {
                                 format!("{} ({})", dependency.name, rename)
                             } else {
@@ -273,12 +884,27 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                             };
                             table.push(Dep {
                                 name: display_name,
+                                table: table_label.clone(),
                                 old_version_req: None,
+                                locked_versions: locked_versions(
+                                    &metadata.packages,
+                                    &dependency.name,
+                                    None,
+                                    maybe_reason == Reason::GitSource,
+                                ),
                                 compatible_version: None,
                                 latest_version: None,
                                 new_version_req: None,
                                 reason,
+                                note,
+                                registry_page: None,
+                                rust_version_held: false,
+                                inherited: false,
                             });
+                            if is_workspace_table {
+                                let row = table.last().expect("just pushed").clone();
+                                workspace_deps.insert(dep_key.to_owned(), row);
+                            }
                         } else {
                             args.verbose(|| {
                                 let source = dependency
@@ -296,6 +922,13 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                     }
                 };
 
+                let mut index_registry = None;
+                let mut vulnerability = None;
+                let mut rust_version_held = false;
+                let mut capped = false;
+                let hold = holds.get(&dependency.name);
+                let mut trace = None;
+                let mut index_krate = None;
                 let (latest_compatible, latest_incompatible) = if dependency
                     .source
                     .as_ref()
@@ -305,17 +938,75 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                     // Update indices for any alternative registries, unless
                     // we're offline.
                     let registry_url = registry_url(&manifest_path, dependency.registry())?;
-                    let krate = index.krate(&registry_url, &dependency.name)?;
-                    let versions = krate
+                    index_krate = index.krate(&registry_url, &dependency.name)?;
+                    let all_versions = index_krate
                         .as_ref()
                         .map(|k| k.versions.as_slice())
                         .unwrap_or_default();
-                    let is_prerelease = old_version_req.contains('-');
+                    // Releases below the requirement's minimum would be downgrades
+                    let floor = VersionReq::parse(&old_version_req)
+                        .ok()
+                        .and_then(|req| req_minimum(&req));
+                    let above_floor = all_versions
+                        .iter()
+                        .filter(|v| {
+                            v.version.parse::<semver::Version>().is_ok_and(|v| {
+                                floor
+                                    .as_ref()
+                                    .is_none_or(|floor| floor.cmp_precedence(&v).is_le())
+                            })
+                        })
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    // `--coherent` holds cap the versions like `# cargo-upgrade: max` does
+                    let below_cap;
+                    let versions = if max.is_some() || hold.is_some() {
+                        below_cap = above_floor
+                            .iter()
+                            .filter(|v| {
+                                v.version.parse::<semver::Version>().is_ok_and(|v| {
+                                    max.is_none_or(|max| max.matches(&v))
+                                        && hold.is_none_or(|hold| v <= hold.version)
+                                })
+                            })
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        below_cap.as_slice()
+                    } else {
+                        above_floor.as_slice()
+                    };
+                    index_registry = Some(registry_url);
+                    let prerelease = args.prerelease_policy(&dependency.name);
+                    let current_pre = VersionReq::parse(&old_version_req)
+                        .ok()
+                        .and_then(|req| {
+                            req.comparators
+                                .into_iter()
+                                .map(|c| c.pre)
+                                .find(|pre| !pre.is_empty())
+                        })
+                        .unwrap_or(semver::Prerelease::EMPTY);
+                    if explaining {
+                        trace = Some(Trace::new(
+                            all_versions,
+                            floor.as_ref(),
+                            max,
+                            prerelease,
+                            &current_pre,
+                            rust_version,
+                        ));
+                    }
 
                     let latest_compatible = VersionReq::parse(&old_version_req)
                         .ok()
                         .and_then(|old_version_req| {
-                            find_compatible_version(versions, &old_version_req, rust_version)
+                            find_compatible_version(
+                                versions,
+                                &old_version_req,
+                                prerelease,
+                                &current_pre,
+                                rust_version,
+                            )
                         })
                         .map(|d| {
                             d.version()
@@ -323,12 +1014,33 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                                 .to_owned()
                         });
 
-                    let latest_version = find_latest_version(versions, is_prerelease, rust_version)
-                        .map(|d| {
-                            d.version()
-                                .expect("registry packages always have a version")
-                                .to_owned()
-                        });
+                    let latest_version =
+                        find_latest_version(versions, prerelease, &current_pre, rust_version).map(
+                            |d| {
+                                d.version()
+                                    .expect("registry packages always have a version")
+                                    .to_owned()
+                            },
+                        );
+
+                    if rust_version.is_some()
+                        && find_latest_version(versions, prerelease, &current_pre, None)
+                            .and_then(|d| d.version().map(|v| v.to_owned()))
+                            != latest_version
+                    {
+                        rust_version_held = true;
+                        report.rust_version_present = true;
+                    }
+                    if max.is_some() {
+                        capped = find_latest_version(
+                            &above_floor,
+                            prerelease,
+                            &current_pre,
+                            rust_version,
+                        )
+                        .and_then(|d| d.version().map(|v| v.to_owned()))
+                            != latest_version;
+                    }
 
                     let latest_incompatible = if latest_version != latest_compatible {
                         latest_version
@@ -336,12 +1048,46 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                         // Its compatible
                         None
                     };
+
+                    // The advisory database only covers crates.io
+                    if let Some(advisory_db) = &mut advisory_db
+                        && dependency.registry().is_none()
+                    {
+                        let locked = metadata
+                            .packages
+                            .iter()
+                            .filter(|p| {
+                                p.name.as_str() == dependency.name
+                                    && p.source.as_ref().is_some_and(|s| s.is_crates_io())
+                            })
+                            .map(|p| p.version.clone())
+                            .collect::<Vec<_>>();
+                        vulnerability = Vulnerability::check(
+                            advisory_db.advisories(&dependency.name)?,
+                            &old_version_req,
+                            &locked,
+                            all_versions,
+                            prerelease,
+                            rust_version,
+                        );
+                    }
                     (latest_compatible, latest_incompatible)
                 } else {
                     (None, None)
                 };
 
-                let is_pinned_dep = dependency.rename.is_some() || is_pinned_req(&old_version_req);
+                // Aliases of a package depended on in several versions each keep their line
+                let alias_ranges = multi_version.get(&dependency.name);
+                let alias_range = alias_ranges.and(req_range(&old_version_req));
+                let collapses = |req: &str| {
+                    alias_ranges
+                        .zip(req_range(req))
+                        .is_some_and(|(ranges, range)| {
+                            Some(range) != alias_range && ranges.contains(&range)
+                        })
+                };
+                let is_pinned_dep = (dependency.rename.is_some() && alias_range.is_none())
+                    || is_pinned_req(&old_version_req);
 
                 let mut new_version_req = if reason.is_some() {
                     Some(old_version_req.clone())
@@ -350,21 +1096,32 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                 };
 
                 if new_version_req.is_none()
-                    && let Some(Some(explicit_version_req)) =
-                        selected_dependencies.get(&dependency.name)
+                    && let Some(explicit_version_req) = selection.version_req(&dependency.name)
                 {
                     if is_pinned_dep && !args.pinned.as_bool() {
                         // `--pinned` is required in case the user meant an unpinned version
                         // in the dependency tree
                         reason.get_or_insert(Reason::Pinned);
-                        pinned_present = true;
+                        report.pinned_present = true;
+                    } else if collapses(explicit_version_req) {
+                        reason.get_or_insert(Reason::MultiVersion);
                     } else {
                         new_version_req = Some(explicit_version_req.to_owned());
                     }
                 }
 
+                let mut incompatible_target = latest_incompatible.clone();
+                if let Some(max) = max
+                    && incompatible_target
+                        .as_ref()
+                        .and_then(|v| v.parse::<semver::Version>().ok())
+                        .is_some_and(|v| !max.matches(&v))
+                {
+                    incompatible_target = None;
+                }
+
                 if new_version_req.is_none()
-                    && let Some(latest_incompatible) = &latest_incompatible
+                    && let Some(latest_incompatible) = &incompatible_target
                 {
                     let new_version: semver::Version = latest_incompatible.parse()?;
                     let req_candidate =
@@ -381,14 +1138,17 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                         };
 
                     if req_candidate.is_some() {
-                        if is_pinned_dep && !args.pinned.as_bool() {
+                        if alias_ranges.is_some_and(|ranges| alias_range < ranges.last().copied()) {
+                            // Only the newest line moves on, the others would end up on it
+                            reason.get_or_insert(Reason::MultiVersion);
+                        } else if is_pinned_dep && !args.pinned.as_bool() {
                             // `--pinned` is required for incompatible upgrades
                             reason.get_or_insert(Reason::Pinned);
-                            pinned_present = true;
+                            report.pinned_present = true;
                         } else if !args.incompatible.as_bool() && !is_pinned_dep {
                             // `--incompatible` is required for non-pinned deps
                             reason.get_or_insert(Reason::Incompatible);
-                            incompatible_present = true;
+                            report.incompatible_present = true;
                         } else {
                             new_version_req = req_candidate;
                         }
@@ -422,14 +1182,109 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                     }
                 }
 
+                if let Some(vulnerability) = &vulnerability {
+                    let verb = if vulnerability.affected.len() == 1 {
+                        "is"
+                    } else {
+                        "are"
+                    };
+                    let warning = format!(
+                        "{} {} {verb} affected by {}",
+                        dependency.name,
+                        vulnerability.affected.join(" and "),
+                        vulnerability
+                            .advisories
+                            .iter()
+                            .map(|a| a.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                    let fix_req = vulnerability.fix_req(&old_version_req);
+                    let warning = if reason == Some(Reason::Excluded) {
+                        format!("{warning}, not upgrading as it is excluded")
+                    } else if reason == Some(Reason::Held) {
+                        format!("{warning}, not upgrading as it is held")
+                    } else if let Some(new_version_req) = &new_version_req
+                        && *new_version_req != old_version_req
+                        && !vulnerability.affects_req(new_version_req)
+                    {
+                        format!("{warning}, fixed by upgrading to `{new_version_req}`")
+                    } else if let Some(fix_req) = fix_req {
+                        // Security fixes take precedence over `--incompatible` and `--pinned`
+                        reason = Some(Reason::Advisory);
+                        let warning = format!("{warning}, upgrading to `{fix_req}`");
+                        new_version_req = Some(fix_req);
+                        warning
+                    } else {
+                        format!("{warning}, no patched version is available")
+                    };
+                    advisory_warnings.push(warning);
+                }
+
                 let new_version_req = new_version_req.unwrap_or_else(|| old_version_req.clone());
 
+                if hold.is_some() && matches!(reason, None | Some(Reason::Latest)) {
+                    reason = Some(Reason::Blocked);
+                } else if capped && matches!(reason, None | Some(Reason::Latest)) {
+                    reason = Some(Reason::Capped);
+                }
                 if new_version_req == old_version_req {
                     reason.get_or_insert(Reason::Latest);
                 } else {
+                    if args.bump_rust_version
+                        && let Some(krate) = &index_krate
+                        && let Some(required) =
+                            required_rust_version_of(&krate.versions, &new_version_req)
+                    {
+                        if is_workspace_table {
+                            workspace_rust_versions.insert(dep_key.to_owned(), required);
+                        } else {
+                            raise_rust_version(
+                                &mut required_rust_version,
+                                required,
+                                &dependency.name,
+                            );
+                        }
+                    }
                     set_dep_version(dep_item, &new_version_req)?;
                     crate_modified = true;
                     modified_crates.insert(dependency.name.clone());
+                    upgrade_plan.push(PlannedEdit {
+                        manifest: plan::relative_manifest_path(
+                            &manifest_path,
+                            metadata.workspace_root.as_std_path(),
+                        ),
+                        member: pkg_name.clone(),
+                        table: table_path.clone(),
+                        key: dep_key.to_owned(),
+                        name: dependency.name.clone(),
+                        old_req: old_version_req.clone(),
+                        new_req: new_version_req.clone(),
+                        tag: false,
+                        index: index_registry.map(|registry| IndexSnapshot {
+                            registry: registry.to_string(),
+                            compatible: latest_compatible.clone(),
+                            latest: latest_incompatible
+                                .clone()
+                                .or_else(|| latest_compatible.clone()),
+                        }),
+                    });
+                }
+
+                if explaining {
+                    let releases = trace
+                        .map(|trace| trace.finish(&old_version_req, &new_version_req, reason))
+                        .unwrap_or_default();
+                    explanations.push(Explanation {
+                        name: dependency.name.clone(),
+                        member: pkg_name.clone(),
+                        table: table_label.clone(),
+                        old_req: Some(old_version_req.clone()),
+                        new_req: Some(new_version_req.clone()),
+                        reason,
+                        releases,
+                        inherited: false,
+                    });
                 }
 
                 let display_name = if let Some(rename) = &dependency.rename {
@@ -438,83 +1293,303 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
                     dependency.name.clone()
                 };
                 let compatible_version = latest_compatible;
-                let latest_version = latest_incompatible.or_else(|| compatible_version.clone());
+                let latest_version = match hold {
+                    Some(hold) => Some(hold.latest.to_string()),
+                    None => latest_incompatible.or_else(|| compatible_version.clone()),
+                };
                 table.push(Dep {
                     name: display_name,
+                    table: table_label.clone(),
+                    locked_versions: locked_versions(
+                        &metadata.packages,
+                        &dependency.name,
+                        Some(&old_version_req),
+                        false,
+                    ),
                     old_version_req: Some(old_version_req),
                     compatible_version,
                     latest_version,
                     new_version_req: Some(new_version_req),
                     reason,
+                    note,
+                    registry_page: dependency
+                        .registry()
+                        .is_none()
+                        .then(|| format!("https://crates.io/crates/{}", dependency.name)),
+                    rust_version_held,
+                    inherited: false,
                 });
+                if is_workspace_table {
+                    let row = table.last().expect("just pushed").clone();
+                    workspace_deps.insert(dep_key.to_owned(), row);
+                }
             }
         }
-        if !table.is_empty() {
-            let (interesting, uninteresting) = table
-                .into_iter()
-                .partition::<Vec<_>, _>(|d| d.show_for(args.verbose_num()));
-            print_upgrade(interesting)?;
-            uninteresting_crates.extend(uninteresting);
+        if is_root && upgrade_patches {
+            for pin in patch::tag_pins(&manifest)? {
+                report.processed_keys.insert(pin.name.clone());
+                let mut reason = None;
+                if !selection.selects(&pin.name) {
+                    reason.get_or_insert(Reason::Excluded);
+                }
+                let max = match pin.annotation.as_ref().map(|a| &a.directive) {
+                    Some(Directive::Hold) => {
+                        reason.get_or_insert(Reason::Held);
+                        None
+                    }
+                    Some(Directive::Max(max)) => Some(max),
+                    None => None,
+                };
+                let note = pin.annotation.as_ref().and_then(|a| a.reason.clone());
+
+                let (compatible_tag, latest_tag, capped) = if reason != Some(Reason::Excluded) {
+                    match patch::find_tags(&pin, args.prerelease_policy(&pin.name), max) {
+                        Ok(Some(tags)) => (tags.compatible, tags.latest, tags.capped),
+                        Ok(None) => {
+                            args.verbose(|| {
+                                shell_warn(&format!(
+                                    "ignoring {}, tag `{}` is not a version",
+                                    pin.key, pin.tag
+                                ))
+                            })?;
+                            continue;
+                        }
+                        Err(err) => {
+                            shell_warn(&format!("ignoring {}, {err}", pin.key))?;
+                            continue;
+                        }
+                    }
+                } else {
+                    (None, None, false)
+                };
+
+                let excluded = reason.is_some();
+                let mut new_tag = None;
+                if !excluded && latest_tag.is_some() && latest_tag != compatible_tag {
+                    if args.incompatible.as_bool() {
+                        new_tag = latest_tag.clone();
+                    } else {
+                        reason.get_or_insert(Reason::Incompatible);
+                        report.incompatible_present = true;
+                    }
+                }
+                if !excluded
+                    && new_tag.is_none()
+                    && compatible_tag.as_ref().is_some_and(|t| *t != pin.tag)
+                {
+                    if args.compatible.as_bool() {
+                        new_tag = compatible_tag.clone();
+                    } else {
+                        reason.get_or_insert(Reason::Compatible);
+                    }
+                }
+
+                let new_tag = new_tag.unwrap_or_else(|| pin.tag.clone());
+                if capped && reason.is_none() {
+                    reason = Some(Reason::Capped);
+                }
+                if new_tag == pin.tag {
+                    reason.get_or_insert(Reason::Latest);
+                } else {
+                    let pin_table = manifest.get_table_mut(&pin.table)?;
+                    patch::set_tag(&pin.table, pin_table, &pin.key, &new_tag)?;
+                    crate_modified = true;
+                    modified_crates.insert(pin.name.clone());
+                    upgrade_plan.push(PlannedEdit {
+                        manifest: plan::relative_manifest_path(
+                            &manifest_path,
+                            metadata.workspace_root.as_std_path(),
+                        ),
+                        member: pkg_name.clone(),
+                        table: pin.table.clone(),
+                        key: pin.key.clone(),
+                        name: pin.name.clone(),
+                        old_req: pin.tag.clone(),
+                        new_req: new_tag.clone(),
+                        tag: true,
+                        index: None,
+                    });
+                }
+
+                table.push(Dep {
+                    name: format!("{} ({})", pin.name, pin.table.join(".")),
+                    table: table_label(&pin.table),
+                    old_version_req: Some(pin.tag),
+                    locked_versions: Vec::new(),
+                    latest_version: latest_tag.or_else(|| compatible_tag.clone()),
+                    compatible_version: compatible_tag,
+                    new_version_req: Some(new_tag),
+                    reason,
+                    note,
+                    registry_page: None,
+                    rust_version_held: false,
+                    inherited: false,
+                });
+            }
+        }
+        member_tables.push(MemberTable {
+            member: pkg_name.clone(),
+            deps: table,
+            explanations,
+            advisory_warnings,
+        });
+        if let Some((required, dep_name)) = required_rust_version {
+            let declared = metadata
+                .packages
+                .iter()
+                .find(|p| p.manifest_path == manifest_path)
+                .and_then(|p| p.rust_version.as_ref())
+                .map(RustVersion::from);
+            if let Some(declared) = declared
+                && declared < required
+            {
+                if manifest.rust_version_is_inherited() {
+                    let (version, members) =
+                        workspace_rust_version.get_or_insert_with(|| (required, Vec::new()));
+                    *version = (*version).max(required);
+                    members.push(pkg_name.clone());
+                } else {
+                    manifest.set_package_rust_version(&required);
+                    crate_modified = true;
+                    shell_status(
+                        "Raising",
+                        &format!(
+                            "{pkg_name}'s rust-version from {declared} to {required}, for {dep_name}"
+                        ),
+                    )?;
+                }
+            }
         }
-        if !args.dry_run && !args.locked && crate_modified {
-            manifest.write()?;
+        if crate_modified {
+            transaction.stage(manifest)?;
         }
     }
 
-    if modified_crates.is_empty() {
-    } else if args.locked {
-        anyhow::bail!("cannot upgrade due to `--locked`");
-    } else if args.dry_run {
-    } else {
-        // Ensure lock file is updated and collect data for `recursive`
-        let metadata = resolve_ws(Some(&root_manifest_path), args.locked, offline)?;
-        let mut locked = metadata.packages;
+    if let Some((required, members)) = workspace_rust_version {
+        let root_manifest = transaction.manifest_mut(&root_manifest_path)?;
+        if let Some(declared) = root_manifest.get_workspace_rust_version()
+            && declared < required
+        {
+            root_manifest.set_workspace_rust_version(&required);
+            shell_status(
+                "Raising",
+                &format!(
+                    "workspace rust-version from {declared} to {required}, for {}",
+                    members.join(", ")
+                ),
+            )?;
+        }
+    }
+
+    let applying = !(modified_crates.is_empty()
+        || args.locked
+        || args.dry_run
+        || args.explain.is_some()
+        || args.plan_out.is_some());
+    if !applying || args.verify.is_none() {
+        for member_table in member_tables.drain(..) {
+            report_member_table(args, report, member_table, &BTreeSet::new())?;
+        }
+    }
+    if args.diff {
+        for manifest in transaction.modified() {
+            manifest.write_diff()?;
+        }
+    }
+
+    let mut lockfile_changes = None;
+    if applying {
+        let before = lockfile::Snapshot::new(metadata);
+        let mut journal =
+            EditJournal::new(metadata.target_directory.as_std_path(), "cargo upgrade");
+        for manifest in transaction.modified() {
+            journal.record(&manifest.path)?;
+        }
+        journal.record(&metadata.workspace_root.as_std_path().join("Cargo.lock"))?;
 
-        let precise_deps = selected_dependencies
+        let precise_deps = report
+            .processed_keys
             .iter()
-            .filter_map(|(name, req)| {
-                req.as_ref()
+            .filter(|name| selection.selects(name))
+            .filter_map(|name| {
+                selection
+                    .version_req(name)
                     .and_then(|req| VersionReq::parse(req).ok())
                     .and_then(|req| {
                         let precise = precise_version(&req)?;
-                        Some((name, (req, precise)))
+                        Some((name.clone(), (req, precise)))
                     })
             })
             .collect::<BTreeMap<_, _>>();
-        if !precise_deps.is_empty() {
+        let recursive = args.recursive.unwrap_or_else(|| args.compatible.as_bool());
+
+        // Ensure lock file is updated and collect data for `recursive`
+        let original = metadata;
+        let mut metadata = None;
+        let workspace_root = root_manifest_path.parent().expect("at least a parent");
+        if let Some(command) = &args.verify {
+            let verification =
+                verify::verify_upgrades(command, workspace_root, upgrade_plan.edits())?;
+            verification.print()?;
+            let reverted = verification.reverted();
+            for member_table in member_tables.drain(..) {
+                report_member_table(args, report, member_table, &reverted)?;
+            }
+            modified_crates.retain(|name| !reverted.contains(name));
+            metadata = Some(resolve_ws(Some(&root_manifest_path), args.locked, offline)?);
+        } else if args.commit_each {
+            // Each commit carries its own lock file changes
+            commit::commit_each(workspace_root, upgrade_plan.edits(), |name| {
+                let metadata = revalidate_ws(original, &root_manifest_path, args.locked, offline)?;
+                if let Some((req, precise)) = precise_deps.get(name) {
+                    lock_precise(
+                        &root_manifest_path,
+                        args.locked,
+                        name,
+                        req,
+                        precise,
+                        &metadata.packages,
+                    )?;
+                } else if recursive {
+                    shell_status("Upgrading", &format!("{name}'s recursive dependencies"))?;
+                    update_recursive(
+                        &root_manifest_path,
+                        args.locked,
+                        &[name],
+                        &metadata.packages,
+                    )?;
+                }
+                Ok(())
+            })?;
+            let offline = true; // index should already be updated
+            metadata = Some(resolve_ws(Some(&root_manifest_path), args.locked, offline)?);
+        } else {
+            transaction.commit(|| {
+                metadata = Some(revalidate_ws(
+                    original,
+                    &root_manifest_path,
+                    args.locked,
+                    offline,
+                )?);
+                Ok(())
+            })?;
+        }
+        journal.save()?;
+        let mut locked = metadata.expect("set by a successful commit").packages;
+
+        if !precise_deps.is_empty() && !args.commit_each {
             // Rollback the updates to the precise version
             //
             // Reusing updates (resolve_ws) so we know what lock_version to reference
             for (name, (req, precise)) in &precise_deps {
-                for lock_version in locked
-                    .iter()
-                    .filter(|p| p.name.as_str() == *name)
-                    .map(|p| &p.version)
-                    .filter(|v| req.matches(v))
-                {
-                    let mut cmd = std::process::Command::new("cargo");
-                    cmd.arg("update");
-                    cmd.arg("--manifest-path").arg(&root_manifest_path);
-                    if args.locked {
-                        cmd.arg("--locked");
-                    }
-                    // NOTE: This will skip the official recursive check and we don't
-                    // recursively update its dependencies
-                    let dep = format!("{name}@{lock_version}");
-                    cmd.arg("--precise").arg(precise);
-                    cmd.arg("--package").arg(dep);
-                    // If we're going to request an update, it would have already been done by now
-                    cmd.arg("--offline");
-                    log::trace!("Running {cmd:?}");
-                    let output = cmd.output().context("failed to lock to precise version")?;
-                    if !output.status.success() {
-                        return Err(anyhow::format_err!(
-                            "{}",
-                            String::from_utf8_lossy(&output.stderr)
-                        ))
-                        .context("failed to lock to precise version");
-                    }
-                }
+                lock_precise(
+                    &root_manifest_path,
+                    args.locked,
+                    name,
+                    req,
+                    precise,
+                    &locked,
+                )?;
             }
 
             // Update data for `recursive` with precise_deps
@@ -528,6 +1603,9 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
             let mut cmd = std::process::Command::new("cargo");
             cmd.arg("update");
             cmd.arg("--manifest-path").arg(&root_manifest_path);
+            if is_script(&root_manifest_path) {
+                cmd.arg("-Zscript");
+            }
             if args.locked {
                 cmd.arg("--locked");
             }
@@ -559,109 +1637,213 @@ fn exec(args: UpgradeArgs) -> CargoResult<()> {
             let offline = true; // index should already be updated
             let metadata = resolve_ws(Some(&root_manifest_path), args.locked, offline)?;
             locked = metadata.packages;
-        }
 
-        if args.recursive.unwrap_or_else(|| args.compatible.as_bool()) {
-            shell_status("Upgrading", "recursive dependencies")?;
-            let mut cmd = std::process::Command::new("cargo");
-            cmd.arg("update");
-            cmd.arg("--manifest-path").arg(&root_manifest_path);
-            if args.locked {
-                cmd.arg("--locked");
+            if args.commit_each {
+                commit::commit_lockfile(workspace_root, "Upgrade git dependencies")?;
             }
-            // Limit recursive update to what we touched
-            cmd.arg("--aggressive");
-            let mut still_run = false;
-            for dep in modified_crates
+        }
+
+        if recursive && !args.commit_each {
+            shell_status("Upgrading", "recursive dependencies")?;
+            let crates = modified_crates
                 .iter()
+                .map(|c| c.as_str())
                 // Already updated so avoid discarding the precise version selection
-                .filter(|c| !precise_deps.contains_key(c))
-            {
-                for lock_version in locked
-                    .iter()
-                    .filter(|p| p.name.as_str() == *dep)
-                    .map(|p| &p.version)
-                {
-                    let dep = format!("{dep}@{lock_version}");
-                    cmd.arg("--package").arg(dep);
-                    still_run = true;
-                }
-            }
-            // If we're going to request an update, it would have already been done by now
-            cmd.arg("--offline");
-            if still_run {
-                log::trace!("Running {cmd:?}");
-                let status = cmd.status().context("recursive dependency update failed")?;
-                if !status.success() {
-                    anyhow::bail!("recursive dependency update failed");
+                .filter(|c| !precise_deps.contains_key(*c))
+                .collect::<Vec<_>>();
+            update_recursive(&root_manifest_path, args.locked, &crates, &locked)?;
+        }
+
+        journal.save()?;
+
+        let offline = true; // index should already be updated
+        let after = resolve_ws(Some(&root_manifest_path), args.locked, offline)?;
+        lockfile_changes = Some(before.diff(&lockfile::Snapshot::new(&after)));
+    }
+
+    if !modified_crates.is_empty() && args.locked {
+        anyhow::bail!("cannot upgrade due to `--locked`");
+    }
+    if let Some(changes) = lockfile_changes {
+        match &mut report.summary {
+            Some(summary) => {
+                if args.summary_out.is_some() {
+                    lockfile::print_changes(&changes)?;
                 }
+                summary.add_lockfile_changes(changes);
             }
+            None => lockfile::print_changes(&changes)?,
         }
     }
 
-    let unused = selected_dependencies
-        .keys()
-        .filter(|k| !processed_keys.contains(k.as_str()))
-        .map(|k| k.as_str())
-        .collect::<Vec<_>>();
-    match unused.len() {
-        0 => {}
-        1 => anyhow::bail!("dependency {} doesn't exist", unused.join(", ")),
-        _ => anyhow::bail!("dependencies {} don't exist", unused.join(", ")),
+    // Under `--dry-run`, `Cargo.lock` was never regenerated for the upgraded requirements
+    if !args.dry_run {
+        let lock_path = metadata.workspace_root.as_std_path().join("Cargo.lock");
+        for patch in patch::unused_patches(&lock_path)? {
+            shell_warn(&format!(
+                "patch `{patch}` is unused, no dependency in the workspace requires a matching version"
+            ))?;
+        }
     }
 
-    if pinned_present {
-        shell_note("Re-run with `--pinned` to upgrade pinned version requirements")?;
+    if let Some(plan_path) = &args.plan_out {
+        upgrade_plan.save(plan_path)?;
+        shell_status("Saved", &format!("upgrade plan to {}", plan_path.display()))?;
     }
-    if incompatible_present {
-        shell_note("Re-run with `--incompatible` to upgrade incompatible version requirements")?;
+
+    Ok(())
+}
+
+/// The `rust-version` of the newest release `version_req` allows
+fn required_rust_version_of(
+    versions: &[tame_index::IndexVersion],
+    version_req: &str,
+) -> Option<RustVersion> {
+    let version_req = VersionReq::parse(version_req).ok()?;
+    let (newest, _) = installable_versions(versions, None)
+        .filter(|(_, version)| version_req.matches(version))
+        .max_by_key(|(_, version)| version.clone())?;
+    newest.rust_version.as_ref()?.parse().ok()
+}
+
+/// Keep the higher of `required` and the `rust-version` already required
+fn raise_rust_version(
+    required_rust_version: &mut Option<(RustVersion, String)>,
+    required: RustVersion,
+    dep_name: &str,
+) {
+    if required_rust_version
+        .as_ref()
+        .is_none_or(|(current, _)| *current < required)
+    {
+        *required_rust_version = Some((required, dep_name.to_owned()));
     }
+}
 
-    if !uninteresting_crates.is_empty() {
-        let mut categorize = BTreeMap::new();
-        for dep in uninteresting_crates {
-            categorize
-                .entry(dep.long_reason())
-                .or_insert_with(BTreeSet::new)
-                .insert(dep.name);
+/// Roll back the locked versions of `name` that match `req` to `precise`
+fn lock_precise(
+    root_manifest_path: &Path,
+    locked_flag: bool,
+    name: &str,
+    req: &VersionReq,
+    precise: &str,
+    locked: &[cargo_metadata::Package],
+) -> CargoResult<()> {
+    for lock_version in locked
+        .iter()
+        .filter(|p| p.name.as_str() == name)
+        .map(|p| &p.version)
+        .filter(|v| req.matches(v))
+    {
+        let mut cmd = std::process::Command::new("cargo");
+        cmd.arg("update");
+        cmd.arg("--manifest-path").arg(root_manifest_path);
+        if is_script(root_manifest_path) {
+            cmd.arg("-Zscript");
         }
-        let verbose_flags = if args.is_verbose() {
-            "`--verbose --verbose`"
-        } else {
-            "`--verbose`"
-        };
-        let mut note = format!("Re-run with {verbose_flags} to show more dependencies");
-        for (reason, deps) in categorize {
-            use std::fmt::Write;
-            write!(&mut note, "\n  {reason}: ")?;
-            if deps.len() <= 3 {
-                for (i, dep) in deps.into_iter().enumerate() {
-                    if 0 < i {
-                        note.push_str(", ");
-                    }
-                    note.push_str(&dep);
-                }
-            } else {
-                let number = deps.len();
-                let plural = if number == 1 { "" } else { "s" };
-                write!(&mut note, "{number} package{plural}")?;
-            }
+        if locked_flag {
+            cmd.arg("--locked");
+        }
+        // NOTE: This will skip the official recursive check and we don't
+        // recursively update its dependencies
+        let dep = format!("{name}@{lock_version}");
+        cmd.arg("--precise").arg(precise);
+        cmd.arg("--package").arg(dep);
+        // If we're going to request an update, it would have already been done by now
+        cmd.arg("--offline");
+        log::trace!("Running {cmd:?}");
+        let output = cmd.output().context("failed to lock to precise version")?;
+        if !output.status.success() {
+            return Err(anyhow::format_err!(
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            ))
+            .context("failed to lock to precise version");
         }
-        shell_note(&note)?;
     }
+    Ok(())
+}
 
-    if args.dry_run {
-        shell_warn("aborting upgrade due to dry run")?;
+/// Update the locked dependencies of `crates`, limited to what they pull in
+fn update_recursive(
+    root_manifest_path: &Path,
+    locked_flag: bool,
+    crates: &[&str],
+    locked: &[cargo_metadata::Package],
+) -> CargoResult<()> {
+    let mut cmd = std::process::Command::new("cargo");
+    cmd.arg("update");
+    cmd.arg("--manifest-path").arg(root_manifest_path);
+    if is_script(root_manifest_path) {
+        cmd.arg("-Zscript");
+    }
+    if locked_flag {
+        cmd.arg("--locked");
+    }
+    // Limit recursive update to what we touched
+    cmd.arg("--aggressive");
+    let mut still_run = false;
+    for dep in crates {
+        for lock_version in locked
+            .iter()
+            .filter(|p| p.name.as_str() == *dep)
+            .map(|p| &p.version)
+        {
+            let dep = format!("{dep}@{lock_version}");
+            cmd.arg("--package").arg(dep);
+            still_run = true;
+        }
+    }
+    // If we're going to request an update, it would have already been done by now
+    cmd.arg("--offline");
+    if still_run {
+        log::trace!("Running {cmd:?}");
+        let status = cmd.status().context("recursive dependency update failed")?;
+        if !status.success() {
+            anyhow::bail!("recursive dependency update failed");
+        }
     }
-
     Ok(())
 }
 
-fn resolve_ws(
+pub(crate) fn resolve_ws(
     manifest_path: Option<&Path>,
     locked: bool,
     offline: bool,
 ) -> CargoResult<cargo_metadata::Metadata> {
+    let mut cmd = metadata_command(manifest_path, locked, offline);
+    let ws = cmd.exec().or_else(|_| {
+        cmd.no_deps();
+        cmd.exec()
+    })?;
+    Ok(ws)
+}
+
+/// Resolve the workspace of `metadata` again after editing its manifests
+///
+/// Unlike [`resolve_ws`], this fails when the workspace resolved before the edits but no longer
+/// does, so the edits can be rolled back.
+pub(crate) fn revalidate_ws(
+    metadata: &cargo_metadata::Metadata,
+    root_manifest_path: &Path,
+    locked: bool,
+    offline: bool,
+) -> CargoResult<cargo_metadata::Metadata> {
+    if metadata.resolve.is_none() {
+        // Never resolved, so there is nothing to break
+        return resolve_ws(Some(root_manifest_path), locked, offline);
+    }
+    let ws = metadata_command(Some(root_manifest_path), locked, offline)
+        .exec()
+        .context("upgraded dependencies failed to resolve")?;
+    Ok(ws)
+}
+
+fn metadata_command(
+    manifest_path: Option<&Path>,
+    locked: bool,
+    offline: bool,
+) -> cargo_metadata::MetadataCommand {
     let mut cmd = cargo_metadata::MetadataCommand::new();
     if let Some(manifest_path) = manifest_path {
         cmd.manifest_path(manifest_path);
@@ -674,13 +1856,92 @@ fn resolve_ws(
     if offline {
         other.push("--offline".to_owned());
     }
+    if manifest_path.is_some_and(is_script) {
+        other.push("-Zscript".to_owned());
+    }
     cmd.other_options(other);
+    cmd
+}
 
-    let ws = cmd.exec().or_else(|_| {
-        cmd.no_deps();
-        cmd.exec()
-    })?;
-    Ok(ws)
+/// Keys of `[workspace.dependencies]` inherited with `workspace = true` in the tables selected
+/// by `--kind` and `--target`
+fn inherited_keys<'p>(
+    args: &UpgradeArgs,
+    manifest_paths: impl Iterator<Item = &'p PathBuf>,
+) -> CargoResult<BTreeSet<String>> {
+    let mut keys = BTreeSet::new();
+    for manifest_path in manifest_paths {
+        let manifest = LocalManifest::try_new(manifest_path)?;
+        for (dep_table, item) in manifest.get_sections() {
+            if !args.selects_table(&dep_table) {
+                continue;
+            }
+            let Some(item) = item.as_table_like() else {
+                continue;
+            };
+            for (key, dep_item) in item.iter() {
+                let inherited = dep_item
+                    .get("workspace")
+                    .and_then(|w| w.as_bool())
+                    .unwrap_or(false);
+                if inherited {
+                    keys.insert(key.to_owned());
+                }
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// The member whose directory contains the current directory
+fn current_member(members: &[cargo_metadata::Package]) -> CargoResult<String> {
+    let cwd = std::env::current_dir().context("failed to read the current directory")?;
+    let cwd = dunce::canonicalize(&cwd).unwrap_or(cwd);
+    members
+        .iter()
+        .filter_map(|member| {
+            let dir = member.manifest_path.parent()?.as_std_path();
+            let dir = dunce::canonicalize(dir).unwrap_or_else(|_| dir.to_owned());
+            cwd.starts_with(&dir)
+                .then(|| (dir.components().count(), member.name.as_str()))
+        })
+        .max()
+        .map(|(_, name)| name.to_owned())
+        .context("`--current-member` requires running from a workspace member's directory")
+}
+
+/// The table a dependency is declared in, as shown in the report
+fn table_label(table_path: &[String]) -> String {
+    match DepTable::from_path(table_path) {
+        Some(dep_table) => dep_table.to_string(),
+        None if table_path[0] == "workspace" => "workspace".to_owned(),
+        None => table_path.join("."),
+    }
+}
+
+/// The cfg `rustc` sets for the `triple` target
+fn target_cfg(triple: &str) -> CargoResult<Vec<Cfg>> {
+    let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = std::process::Command::new(rustc)
+        .args(["--print", "cfg", "--target", triple])
+        .output()
+        .context("failed to run `rustc`")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "failed to get the cfg of target `{triple}`:\n{}",
+        String::from_utf8_lossy(&output.stderr).trim_end()
+    );
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| {
+            line.parse::<Cfg>()
+                .with_context(|| format!("invalid cfg `{line}` for target `{triple}`"))
+        })
+        .collect()
+}
+
+fn strip_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
 }
 
 fn find_ws_members(ws: &cargo_metadata::Metadata) -> Vec<cargo_metadata::Package> {
@@ -692,6 +1953,235 @@ fn find_ws_members(ws: &cargo_metadata::Metadata) -> Vec<cargo_metadata::Package
         .collect()
 }
 
+/// Versions of `name` in `Cargo.lock` that `version_req` resolved to, from a git repository for
+/// `git`
+fn locked_versions(
+    packages: &[cargo_metadata::Package],
+    name: &str,
+    version_req: Option<&str>,
+    git: bool,
+) -> Vec<semver::Version> {
+    let version_req = version_req.and_then(|r| VersionReq::parse(r).ok());
+    let mut versions = packages
+        .iter()
+        .filter(|p| p.name.as_str() == name)
+        .filter(|p| {
+            p.source
+                .as_ref()
+                .is_some_and(|s| s.repr.starts_with("git+") == git)
+        })
+        .filter(|p| version_req.as_ref().is_none_or(|r| r.matches(&p.version)))
+        .map(|p| p.version.clone())
+        .collect::<Vec<_>>();
+    versions.sort();
+    versions.dedup();
+    versions
+}
+
+/// Direct registry dependencies for `--coherent`, with the releases each may move to
+///
+/// `manifests` are those of the workspace, its root first.
+fn coherent_candidates(
+    args: &UpgradeArgs,
+    metadata: &cargo_metadata::Metadata,
+    manifests: &[(String, PathBuf, Option<RustVersion>)],
+    member_selection: &MemberSelection,
+    selection: &Selection,
+    inherited_keys: Option<&BTreeSet<String>>,
+    index: &mut IndexCache,
+) -> CargoResult<BTreeMap<String, Candidate>> {
+    struct Entry {
+        registry: url::Url,
+        version_reqs: Vec<String>,
+        /// Whether any entry may take an incompatible upgrade
+        moving: bool,
+        /// The entries that can't take an incompatible upgrade, like "two keeps foo `=0.1.0`"
+        stuck: Vec<String>,
+    }
+
+    let root_manifest_path = &manifests[0].1;
+    // Releases must suit every member
+    let rust_version = manifests
+        .iter()
+        .map(|(_, _, rust_version)| *rust_version)
+        .min_by_key(|rust_version| rust_version.unwrap_or(RustVersion::MAX))
+        .flatten();
+    let mut entries: BTreeMap<String, Entry> = BTreeMap::new();
+    for (pkg_name, manifest_path, _) in manifests {
+        let is_root = manifest_path == root_manifest_path;
+        let member_selected = member_selection.selects(pkg_name);
+        let mut manifest = LocalManifest::try_new(manifest_path)?;
+        let aliases = multi_version_packages(&mut manifest, manifest_path)?;
+        for table_path in manifest.get_dependency_table_paths() {
+            let is_workspace_table = table_path[0] == "workspace";
+            let table_selected = match DepTable::from_path(&table_path) {
+                Some(dep_table) => member_selected && args.selects_table(&dep_table),
+                None => is_root,
+            };
+            let dep_table = manifest
+                .get_table_mut(&table_path)?
+                .as_table_like_mut()
+                .expect("dependency table paths always point to tables");
+            for (dep_key, dep_item) in dep_table.iter() {
+                let Ok(dependency) = Dependency::from_toml(manifest_path, dep_key, dep_item) else {
+                    continue;
+                };
+                let Some(version_req) = dependency.version() else {
+                    continue;
+                };
+                if dependency
+                    .source
+                    .as_ref()
+                    .and_then(|s| s.as_registry())
+                    .is_none()
+                {
+                    continue;
+                }
+                let in_scope = table_selected
+                    && (!is_workspace_table || inherited_keys.is_none_or(|k| k.contains(dep_key)));
+                // Like when upgrading, only the newest line of a multi-version package moves on
+                let alias_ranges = aliases.get(&dependency.name);
+                let alias_range = alias_ranges.and(req_range(version_req));
+                if alias_ranges.is_some_and(|ranges| alias_range < ranges.last().copied()) {
+                    continue;
+                }
+                let is_pinned_dep = (dependency.rename.is_some() && alias_range.is_none())
+                    || is_pinned_req(version_req);
+                let moves = in_scope
+                    && selection.selects(&dependency.name)
+                    && (args.incompatible.as_bool()
+                        || selection.version_req(&dependency.name).is_some())
+                    && (args.pinned.as_bool() || !is_pinned_dep);
+                let registry = registry_url(manifest_path, dependency.registry())?;
+                let entry = entries
+                    .entry(dependency.name.clone())
+                    .or_insert_with(|| Entry {
+                        registry,
+                        version_reqs: Vec::new(),
+                        moving: false,
+                        stuck: Vec::new(),
+                    });
+                entry.version_reqs.push(version_req.to_owned());
+                if moves {
+                    entry.moving = true;
+                } else {
+                    entry.stuck.push(format!(
+                        "{pkg_name} keeps {} `{version_req}`",
+                        dependency.name
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut candidates = BTreeMap::new();
+    for (name, entry) in entries {
+        let reqs = entry
+            .version_reqs
+            .iter()
+            .filter_map(|req| VersionReq::parse(req).ok())
+            .collect::<Vec<_>>();
+        let locked = metadata
+            .packages
+            .iter()
+            .filter(|p| p.name.as_str() == name && reqs.iter().any(|r| r.matches(&p.version)))
+            .map(|p| p.version.clone())
+            .max();
+        let Some(current) = locked.or_else(|| reqs.iter().filter_map(req_minimum).max()) else {
+            continue;
+        };
+        let Some(krate) = index.krate(&entry.registry, &name)? else {
+            continue;
+        };
+        let prerelease = args.prerelease_policy(&name);
+        let explicit = selection
+            .version_req(&name)
+            .and_then(|req| VersionReq::parse(req).ok());
+        let mut releases = installable_versions(&krate.versions, rust_version)
+            .filter(|(_, v)| current <= *v && prerelease.allows(&current.pre, v))
+            .filter(|(_, v)| explicit.as_ref().is_none_or(|req| req.matches(v)))
+            .map(|(index_version, v)| Release::new(v, index_version))
+            .collect::<Vec<_>>();
+        releases.sort_by(|a, b| b.version.cmp(&a.version));
+        let mut stuck = None;
+        if !entry.stuck.is_empty() {
+            // Only some entries could move to a newer line, the others keep it on this one
+            if let Some(newest) = releases.first()
+                && entry.moving
+                && compatibility(&newest.version) != compatibility(&current)
+            {
+                stuck = Some((newest.version.clone(), entry.stuck));
+            }
+            releases.retain(|r| compatibility(&r.version) == compatibility(&current));
+        }
+        candidates.insert(
+            name,
+            Candidate {
+                current,
+                releases,
+                stuck,
+            },
+        );
+    }
+    Ok(candidates)
+}
+
+/// Compatibility ranges of each package depended on through aliases, by package name
+type AliasRanges = BTreeMap<String, BTreeSet<(u64, u64, u64)>>;
+
+/// Compatibility ranges of the packages a manifest depends on in several versions, through
+/// renames like `rand07 = { package = "rand", version = "0.7" }`
+fn multi_version_packages(
+    manifest: &mut LocalManifest,
+    manifest_path: &Path,
+) -> CargoResult<AliasRanges> {
+    let mut deps = Vec::new();
+    for table_path in manifest.get_dependency_table_paths() {
+        let dep_table = manifest
+            .get_table_mut(&table_path)?
+            .as_table_like_mut()
+            .expect("dependency table paths always point to tables");
+        for (dep_key, dep_item) in dep_table.iter() {
+            let Ok(dependency) = Dependency::from_toml(manifest_path, dep_key, dep_item) else {
+                continue;
+            };
+            if let Some(version_req) = dependency.version() {
+                deps.push((
+                    dependency.name.clone(),
+                    dependency.rename.is_some(),
+                    version_req.to_owned(),
+                ));
+            }
+        }
+    }
+    Ok(group_aliases(&deps))
+}
+
+/// Group `(package, renamed, version_req)` by package, keeping those in several compatibility
+/// ranges through a rename
+fn group_aliases(deps: &[(String, bool, String)]) -> AliasRanges {
+    let mut groups: BTreeMap<String, (bool, BTreeSet<_>)> = BTreeMap::new();
+    for (name, renamed, version_req) in deps {
+        let Some(range) = req_range(version_req) else {
+            continue;
+        };
+        let group = groups.entry(name.clone()).or_default();
+        group.0 |= renamed;
+        group.1.insert(range);
+    }
+    groups
+        .into_iter()
+        .filter(|(_, (renamed, ranges))| *renamed && 1 < ranges.len())
+        .map(|(name, (_, ranges))| (name, ranges))
+        .collect()
+}
+
+/// The compatibility range a version requirement starts in, like `(0, 7, 0)` for `0.7.3`
+fn req_range(version_req: &str) -> Option<(u64, u64, u64)> {
+    let minimum = req_minimum(&VersionReq::parse(version_req).ok()?)?;
+    Some(compatibility(&minimum))
+}
+
 fn is_pinned_req(old_version_req: &str) -> bool {
     if let Ok(version_req) = VersionReq::parse(old_version_req) {
         version_req.comparators.iter().any(|comparator| {
@@ -732,14 +2222,26 @@ fn precise_version(version_req: &VersionReq) -> Option<String> {
         .map(|v| v.to_string())
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct Dep {
     name: String,
+    /// The table declaring the dependency, like `dev` or `workspace`
+    table: String,
     old_version_req: Option<String>,
+    /// Versions in `Cargo.lock` that `old_version_req` resolved to
+    locked_versions: Vec<semver::Version>,
     compatible_version: Option<String>,
     latest_version: Option<String>,
     new_version_req: Option<String>,
     reason: Option<Reason>,
+    /// Why a `# cargo-upgrade:` annotation holds the dependency back
+    note: Option<String>,
+    /// The crate's page on crates.io
+    registry_page: Option<String>,
+    /// Whether `rust-version` kept a newer version out of `latest_version`
+    rust_version_held: bool,
+    /// Whether this mirrors the `[workspace.dependencies]` entry a member inherits
+    inherited: bool,
 }
 
 impl Dep {
@@ -751,6 +2253,17 @@ impl Dep {
         ColorSpec::new()
     }
 
+    fn locked_version(&self) -> String {
+        if self.locked_versions.is_empty() {
+            return "-".to_owned();
+        }
+        self.locked_versions
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     fn compatible_version(&self) -> &str {
         self.compatible_version.as_deref().unwrap_or("-")
     }
@@ -812,6 +2325,15 @@ impl Dep {
         self.reason.map(|r| r.as_long()).unwrap_or("")
     }
 
+    /// The note column: the short reason, followed by the annotation's
+    fn note(&self) -> String {
+        match (self.short_reason(), &self.note) {
+            ("", Some(note)) => note.clone(),
+            (reason, Some(note)) => format!("{reason}: {note}"),
+            (reason, None) => reason.to_owned(),
+        }
+    }
+
     fn reason_spec(&self) -> ColorSpec {
         let mut spec = ColorSpec::new();
         if self.reason.unwrap_or(Reason::Latest).is_warning() {
@@ -825,7 +2347,7 @@ impl Dep {
             return true;
         }
 
-        if self.req_changed() {
+        if self.req_changed() || self.reason == Some(Reason::FailedVerification) {
             return true;
         }
         if 0 < verbosity {
@@ -837,11 +2359,111 @@ impl Dep {
                 // Show excluded cases with potential
                 return true;
             }
+
+            if !self.is_locked_latest() {
+                // Upgrading would move `Cargo.lock`
+                return true;
+            }
         }
 
         false
     }
 
+    /// The package name, without the rename or patch table in `name`
+    fn package(&self) -> &str {
+        self.name
+            .split_once(" (")
+            .map(|(package, _)| package)
+            .unwrap_or(&self.name)
+    }
+
+    fn is_compatible_upgrade(&self) -> bool {
+        if self.new_version_req == self.compatible_version {
+            return true;
+        }
+        if let Some(compatible_version) = self
+            .compatible_version
+            .as_ref()
+            .and_then(|v| semver::Version::parse(v).ok())
+            && let Some(new_version_req) = &self.new_version_req
+            && let Ok(new_version_req) = VersionReq::parse(new_version_req)
+        {
+            return new_version_req.matches(&compatible_version);
+        }
+        false
+    }
+
+    fn summary_row(&self, member: &str) -> Option<SummaryRow> {
+        if self.inherited {
+            // Summarized with the workspace entry
+            return None;
+        }
+        let reason = self.reason.unwrap_or(Reason::Latest);
+        let section = if self.req_changed() {
+            if self.is_compatible_upgrade() {
+                Section::Compatible
+            } else {
+                Section::Incompatible
+            }
+        } else if reason.is_upgradeable()
+            || matches!(
+                reason,
+                Reason::Blocked | Reason::Capped | Reason::FailedVerification
+            )
+            || (reason == Reason::Held && !self.old_req_matches_latest())
+            || self.rust_version_held
+        {
+            Section::HeldBack
+        } else {
+            return None;
+        };
+
+        let mut hints = Vec::new();
+        if section == Section::HeldBack {
+            match reason {
+                Reason::Pinned => hints.push(Hint::Pinned),
+                Reason::Incompatible => hints.push(Hint::Incompatible),
+                _ => {}
+            }
+        }
+        if self.rust_version_held {
+            hints.push(Hint::RustVersion);
+        }
+        let reason = match self.short_reason() {
+            "" if self.rust_version_held => "rust-version",
+            reason => reason,
+        };
+        Some(SummaryRow {
+            member: member.to_owned(),
+            package: self.package().to_owned(),
+            name: self.name.clone(),
+            table: self.table.clone(),
+            link: self.registry_page.clone(),
+            old_req: self.old_version_req().to_owned(),
+            locked: (!self.locked_versions.is_empty()).then(|| self.locked_version()),
+            new_req: self.new_version_req().to_owned(),
+            latest: self.latest_version.clone(),
+            reason,
+            note: self.note.clone(),
+            section,
+            hints,
+        })
+    }
+
+    /// Whether every locked version is the latest, ignoring build metadata
+    fn is_locked_latest(&self) -> bool {
+        let Some(latest_version) = self
+            .latest_version
+            .as_deref()
+            .and_then(|v| semver::Version::parse(v).ok())
+        else {
+            return true;
+        };
+        self.locked_versions
+            .iter()
+            .all(|v| v.cmp_precedence(&latest_version).is_eq())
+    }
+
     fn old_req_matches_latest(&self) -> bool {
         if let Some(latest_version) = self
             .latest_version
@@ -852,12 +2474,18 @@ impl Dep {
         {
             return old_version_req.matches(&latest_version);
         }
+        // `[patch]` tag pins
+        if let Some((_, latest_tag)) = self.latest_version.as_deref().and_then(patch::split_tag)
+            && let Some((_, old_tag)) = self.old_version_req.as_deref().and_then(patch::split_tag)
+        {
+            return old_tag == latest_tag;
+        }
         true
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-enum Reason {
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum Reason {
     Latest,
     Compatible,
     Incompatible,
@@ -865,6 +2493,17 @@ enum Reason {
     GitSource,
     PathSource,
     Excluded,
+    Advisory,
+    /// Another alias of the package is on a newer line
+    MultiVersion,
+    /// Other direct dependencies require the current version
+    Blocked,
+    /// A `# cargo-upgrade: hold` annotation keeps the version requirement
+    Held,
+    /// A `# cargo-upgrade: max` annotation keeps newer versions out
+    Capped,
+    /// `--verify` rolled the upgrade back
+    FailedVerification,
 }
 
 impl Reason {
@@ -877,6 +2516,12 @@ impl Reason {
             Self::GitSource => false,
             Self::PathSource => false,
             Self::Excluded => false,
+            Self::Advisory => false,
+            Self::MultiVersion => false,
+            Self::Blocked => false,
+            Self::Held => false,
+            Self::Capped => false,
+            Self::FailedVerification => false,
         }
     }
 
@@ -889,6 +2534,12 @@ impl Reason {
             Self::GitSource => false,
             Self::PathSource => false,
             Self::Excluded => false,
+            Self::Advisory => true,
+            Self::MultiVersion => false,
+            Self::Blocked => true,
+            Self::Held => false,
+            Self::Capped => false,
+            Self::FailedVerification => true,
         }
     }
 
@@ -901,6 +2552,12 @@ impl Reason {
             Self::GitSource => "git",
             Self::PathSource => "local",
             Self::Excluded => "excluded",
+            Self::Advisory => "advisory",
+            Self::MultiVersion => "multi-version",
+            Self::Blocked => "blocked",
+            Self::Held => "held",
+            Self::Capped => "capped",
+            Self::FailedVerification => "failed verification",
         }
     }
 
@@ -913,6 +2570,50 @@ impl Reason {
             Self::GitSource => "git",
             Self::PathSource => "local",
             Self::Excluded => "excluded",
+            Self::Advisory => "security advisory",
+            Self::MultiVersion => "multi-version",
+            Self::Blocked => "blocked by other dependencies",
+            Self::Held => "held by annotation",
+            Self::Capped => "capped by annotation",
+            Self::FailedVerification => "failed verification",
+        }
+    }
+
+    /// What keeps a release out, for `--explain`
+    pub(crate) fn detail(&self) -> &'static str {
+        match self {
+            Self::Latest => "already the latest",
+            Self::Compatible => "compatible upgrades are turned off",
+            Self::Incompatible => "incompatible with the current requirement",
+            Self::Pinned => "the current requirement is pinned",
+            Self::GitSource => "from git, so only updated in `Cargo.lock`",
+            Self::PathSource => "a local path, so never upgraded",
+            Self::Excluded => "excluded",
+            Self::Advisory => "past the fix for a security advisory",
+            Self::MultiVersion => "another alias of the package is on a newer line",
+            Self::Blocked => "other direct dependencies require an older version",
+            Self::Held => "held by a `# cargo-upgrade: hold` comment",
+            Self::Capped => "above the `# cargo-upgrade: max` bound",
+            Self::FailedVerification => "fails the `--verify` command",
+        }
+    }
+
+    /// How to let a release through, for `--explain`
+    pub(crate) fn remedy(&self) -> Option<&'static str> {
+        match self {
+            Self::Latest => None,
+            Self::Compatible => Some("re-run with `--compatible allow`"),
+            Self::Incompatible => Some("re-run with `--incompatible`"),
+            Self::Pinned => Some("re-run with `--pinned`"),
+            Self::GitSource => None,
+            Self::PathSource => None,
+            Self::Excluded => Some("drop it from `--exclude`, or name it with `--package`"),
+            Self::Advisory => None,
+            Self::MultiVersion => None,
+            Self::Blocked => Some("upgrade those along with it, or drop `--coherent`"),
+            Self::Held => Some("remove the comment"),
+            Self::Capped => Some("raise the bound"),
+            Self::FailedVerification => Some("fix what the `--verify` command reports"),
         }
     }
 }
@@ -927,35 +2628,52 @@ fn print_upgrade(mut interesting: Vec<Dep>) -> CargoResult<()> {
         [
             Dep {
                 name: "name".to_owned(),
+                table: "table".to_owned(),
                 old_version_req: Some("old req".to_owned()),
+                locked_versions: Vec::new(),
                 compatible_version: Some("compatible".to_owned()),
                 latest_version: Some("latest".to_owned()),
                 new_version_req: Some("new req".to_owned()),
                 reason: None,
+                note: None,
+                registry_page: None,
+                rust_version_held: false,
+                inherited: false,
             },
             Dep {
                 name: "====".to_owned(),
+                table: "=====".to_owned(),
                 old_version_req: Some("=======".to_owned()),
+                locked_versions: Vec::new(),
                 compatible_version: Some("==========".to_owned()),
                 latest_version: Some("======".to_owned()),
                 new_version_req: Some("=======".to_owned()),
                 reason: None,
+                note: None,
+                registry_page: None,
+                rust_version_held: false,
+                inherited: false,
             },
         ],
     );
-    let mut width = [0; 6];
+    let mut width = [0; 8];
     for (i, dep) in interesting.iter().enumerate() {
         width[0] = width[0].max(dep.name.len());
-        width[1] = width[1].max(dep.old_version_req().len());
-        width[2] = width[2].max(dep.compatible_version().len());
-        width[3] = width[3].max(dep.latest_version().len());
-        width[4] = width[4].max(dep.new_version_req().len());
+        width[1] = width[1].max(dep.table.len());
+        width[2] = width[2].max(dep.old_version_req().len());
+        if 1 < i {
+            width[3] = width[3].max(dep.locked_version().len());
+        }
+        width[4] = width[4].max(dep.compatible_version().len());
+        width[5] = width[5].max(dep.latest_version().len());
+        width[6] = width[6].max(dep.new_version_req().len());
         if 1 < i {
-            width[5] = width[5].max(dep.short_reason().len());
+            width[7] = width[7].max(dep.note().len());
         }
     }
-    if 0 < width[5] {
-        width[5] = width[5].max("note".len());
+    width[3] = width[3].max("locked".len());
+    if 0 < width[7] {
+        width[7] = width[7].max("note".len());
     }
 
     for (i, dep) in interesting.iter().enumerate() {
@@ -970,13 +2688,29 @@ fn print_upgrade(mut interesting: Vec<Dep>) -> CargoResult<()> {
         };
         write_cell(&dep.name, width[0], &spec)?;
 
+        shell_write_stdout(" ", &ColorSpec::new())?;
+        write_cell(&dep.table, width[1], &spec)?;
+
         shell_write_stdout(" ", &ColorSpec::new())?;
         let spec = if is_header {
             header_spec.clone()
         } else {
             dep.old_version_req_spec()
         };
-        write_cell(dep.old_version_req(), width[1], &spec)?;
+        write_cell(dep.old_version_req(), width[2], &spec)?;
+
+        shell_write_stdout(" ", &ColorSpec::new())?;
+        let spec = if is_header {
+            header_spec.clone()
+        } else {
+            ColorSpec::new()
+        };
+        let locked = match i {
+            0 => "locked".to_owned(),
+            1 => "======".to_owned(),
+            _ => dep.locked_version(),
+        };
+        write_cell(&locked, width[3], &spec)?;
 
         shell_write_stdout(" ", &ColorSpec::new())?;
         let spec = if is_header {
@@ -984,7 +2718,7 @@ fn print_upgrade(mut interesting: Vec<Dep>) -> CargoResult<()> {
         } else {
             dep.compatible_version_spec()
         };
-        write_cell(dep.compatible_version(), width[2], &spec)?;
+        write_cell(dep.compatible_version(), width[4], &spec)?;
 
         shell_write_stdout(" ", &ColorSpec::new())?;
         let spec = if is_header {
@@ -992,7 +2726,7 @@ fn print_upgrade(mut interesting: Vec<Dep>) -> CargoResult<()> {
         } else {
             ColorSpec::new()
         };
-        write_cell(dep.latest_version(), width[3], &spec)?;
+        write_cell(dep.latest_version(), width[5], &spec)?;
 
         shell_write_stdout(" ", &ColorSpec::new())?;
         let spec = if is_header {
@@ -1000,9 +2734,9 @@ fn print_upgrade(mut interesting: Vec<Dep>) -> CargoResult<()> {
         } else {
             dep.new_version_req_spec()
         };
-        write_cell(dep.new_version_req(), width[4], &spec)?;
+        write_cell(dep.new_version_req(), width[6], &spec)?;
 
-        if 0 < width[5] {
+        if 0 < width[7] {
             shell_write_stdout(" ", &ColorSpec::new())?;
             let spec = if is_header {
                 header_spec.clone()
@@ -1010,11 +2744,11 @@ fn print_upgrade(mut interesting: Vec<Dep>) -> CargoResult<()> {
                 dep.reason_spec()
             };
             let reason = match i {
-                0 => "note",
-                1 => "====",
-                _ => dep.short_reason(),
+                0 => "note".to_owned(),
+                1 => "====".to_owned(),
+                _ => dep.note(),
             };
-            write_cell(reason, width[5], &spec)?;
+            write_cell(&reason, width[7], &spec)?;
         }
 
         shell_write_stdout("\n", &ColorSpec::new())?;
@@ -1023,7 +2757,41 @@ fn print_upgrade(mut interesting: Vec<Dep>) -> CargoResult<()> {
     Ok(())
 }
 
-fn write_cell(content: &str, width: usize, spec: &ColorSpec) -> CargoResult<()> {
+/// Write `rows` under a bold `header`, with `last_spec` coloring the last cell of each row
+pub(crate) fn write_table<const N: usize>(
+    header: [&str; N],
+    rows: &[[String; N]],
+    last_spec: impl Fn(usize) -> ColorSpec,
+) -> CargoResult<()> {
+    let header = header.map(str::to_owned);
+    let underline = header.clone().map(|h| "=".repeat(h.len()));
+    let mut width = [0; N];
+    for row in [&header, &underline].into_iter().chain(rows) {
+        for (width, cell) in width.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut header_spec = ColorSpec::new();
+    header_spec.set_bold(true);
+    for (i, row) in [&header, &underline].into_iter().chain(rows).enumerate() {
+        let (spec, last_spec) = match i.checked_sub(2) {
+            None => (header_spec.clone(), header_spec.clone()),
+            Some(i) => (ColorSpec::new(), last_spec(i)),
+        };
+        for (j, cell) in row.iter().enumerate() {
+            if 0 < j {
+                shell_write_stdout(" ", &ColorSpec::new())?;
+            }
+            let spec = if j + 1 == N { &last_spec } else { &spec };
+            write_cell(cell, width[j], spec)?;
+        }
+        shell_write_stdout("\n", &ColorSpec::new())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_cell(content: &str, width: usize, spec: &ColorSpec) -> CargoResult<()> {
     shell_write_stdout(content, spec)?;
     for _ in 0..(width - content.len()) {
         shell_write_stdout(" ", &ColorSpec::new())?;
@@ -1035,6 +2803,24 @@ fn write_cell(content: &str, width: usize, spec: &ColorSpec) -> CargoResult<()>
 mod test {
     use super::*;
 
+    #[test]
+    fn group_renamed_major_lines() {
+        let dep = |name: &str, renamed: bool, req: &str| (name.to_owned(), renamed, req.to_owned());
+        let groups = group_aliases(&[
+            dep("rand", true, "0.7"),
+            dep("rand", false, "0.8.5"),
+            dep("serde", false, "1.0"),
+            dep("serde", false, "1.0.100"),
+            dep("syn", false, "1"),
+            dep("syn", false, "2"),
+            dep("toml", true, "0.5"),
+        ]);
+        assert_eq!(
+            groups,
+            BTreeMap::from([("rand".to_owned(), BTreeSet::from([(0, 7, 0), (0, 8, 0)]))])
+        );
+    }
+
     #[test]
     fn exact_is_pinned_req() {
         let req = "=3";
@@ -1088,4 +2874,27 @@ mod test {
         let req = "3";
         assert!(!is_pinned_req(req));
     }
+
+    #[test]
+    fn locked_latest_ignores_build_metadata() {
+        let dep = |locked: &str, latest: &str| Dep {
+            name: "foo".to_owned(),
+            table: "normal".to_owned(),
+            old_version_req: Some("0.1".to_owned()),
+            locked_versions: locked.split(", ").map(|v| v.parse().unwrap()).collect(),
+            compatible_version: None,
+            latest_version: Some(latest.to_owned()),
+            new_version_req: None,
+            reason: None,
+            note: None,
+            registry_page: None,
+            rust_version_held: false,
+            inherited: false,
+        };
+        assert!(dep("0.1.1+foo", "0.1.1").is_locked_latest());
+        assert!(!dep("0.1.1", "0.1.2").is_locked_latest());
+        assert!(!dep("0.1.1-rc.1", "0.1.1").is_locked_latest());
+        assert!(!dep("0.1.0, 0.1.1", "0.1.1").is_locked_latest());
+        assert!(dep("0.1.1+foo, 0.1.1+bar", "0.1.1").is_locked_latest());
+    }
 }
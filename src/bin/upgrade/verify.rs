@@ -0,0 +1,221 @@
+//! Check upgrades with a command, like a build, keeping only those that pass
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Context as _;
+use cargo_edit::{CargoResult, ManifestTransaction, shell_status, shell_warn, write_atomic};
+
+use crate::plan::{self, PlannedEdit};
+
+/// Lines of the failing command's output shown for each rolled back upgrade
+const EXCERPT_LINES: usize = 5;
+
+/// Which upgrades survived `--verify`
+pub(crate) struct Verification {
+    kept: Vec<Upgrade>,
+    reverted: Vec<(Upgrade, String)>,
+}
+
+impl Verification {
+    /// Names of the packages whose upgrades were rolled back
+    pub(crate) fn reverted(&self) -> BTreeSet<String> {
+        self.reverted.iter().map(|(u, _)| u.name.clone()).collect()
+    }
+
+    pub(crate) fn print(&self) -> CargoResult<()> {
+        for upgrade in &self.kept {
+            shell_status("Kept", &upgrade.to_string())?;
+        }
+        for (upgrade, excerpt) in &self.reverted {
+            shell_warn(&format!(
+                "rolled back {upgrade}, verification failed:\n{excerpt}"
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// All edits for one dependency, across manifests and tables
+struct Upgrade {
+    name: String,
+    old_req: String,
+    new_req: String,
+}
+
+impl Upgrade {
+    fn new(edits: &[&PlannedEdit]) -> Self {
+        let first = edits.first().expect("groups are never empty");
+        Self {
+            name: first.name.clone(),
+            old_req: first.old_req.clone(),
+            new_req: first.new_req.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Upgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} -> {}", self.name, self.old_req, self.new_req)
+    }
+}
+
+/// Write `edits` one dependency at a time, running `command` after each and reverting the
+/// dependencies that make it fail
+///
+/// When all upgrades pass together, they are kept without checking them one by one.
+pub(crate) fn verify_upgrades(
+    command: &str,
+    workspace_root: &Path,
+    edits: &[PlannedEdit],
+) -> CargoResult<Verification> {
+    shell_status("Verifying", "workspace before upgrading")?;
+    if let Err(output) = run(command, workspace_root)? {
+        anyhow::bail!(
+            "`{command}` fails before upgrading:\n{}",
+            error_excerpt(&output)
+        );
+    }
+
+    let groups = plan::group_by_name(edits);
+
+    let mut verification = Verification {
+        kept: Vec::new(),
+        reverted: Vec::new(),
+    };
+    if 1 < groups.len() {
+        shell_status("Verifying", "all upgrades together")?;
+        let all = edits.iter().collect::<Vec<_>>();
+        if try_edits(command, workspace_root, &all)?.is_ok() {
+            verification.kept = groups.values().map(|g| Upgrade::new(g)).collect();
+            return Ok(verification);
+        }
+    }
+
+    for group in groups.values() {
+        let upgrade = Upgrade::new(group);
+        shell_status("Verifying", &upgrade.to_string())?;
+        match try_edits(command, workspace_root, group)? {
+            Ok(()) => verification.kept.push(upgrade),
+            Err(output) => verification
+                .reverted
+                .push((upgrade, error_excerpt(&output))),
+        }
+    }
+    Ok(verification)
+}
+
+/// Write `edits` and run `command`, restoring the manifests and lockfile if it fails
+///
+/// On failure, returns the command's output.
+fn try_edits(
+    command: &str,
+    workspace_root: &Path,
+    edits: &[&PlannedEdit],
+) -> CargoResult<Result<(), String>> {
+    let lock_path = workspace_root.join("Cargo.lock");
+    let original_lock = std::fs::read(&lock_path).ok();
+
+    let mut transaction = ManifestTransaction::new();
+    let conflicts = plan::stage_edits(&mut transaction, workspace_root, edits)?;
+    anyhow::ensure!(
+        conflicts.is_empty(),
+        "manifests changed while verifying upgrades:\n  {}",
+        conflicts.join("\n  ")
+    );
+
+    let mut failure = None;
+    let committed = transaction.commit(|| {
+        if let Err(output) = run(command, workspace_root)? {
+            failure = Some(output);
+            anyhow::bail!("`{command}` failed");
+        }
+        Ok(())
+    });
+    match (committed, failure) {
+        (Ok(()), _) => Ok(Ok(())),
+        (Err(_), Some(output)) => {
+            match original_lock {
+                Some(original_lock) => write_atomic(&lock_path, &original_lock)
+                    .with_context(|| format!("failed to restore {}", lock_path.display()))?,
+                None => {
+                    let _ = std::fs::remove_file(&lock_path);
+                }
+            }
+            Ok(Err(output))
+        }
+        (Err(err), None) => Err(err),
+    }
+}
+
+/// Run `command` through the shell, returning its output on failure
+fn run(command: &str, cwd: &Path) -> CargoResult<Result<(), String>> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command).current_dir(cwd);
+    log::trace!("Running {cmd:?}");
+    let output = cmd
+        .output()
+        .with_context(|| format!("failed to run `{command}`"))?;
+    if output.status.success() {
+        Ok(Ok(()))
+    } else {
+        let mut text = String::from_utf8_lossy(&output.stderr).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stdout));
+        Ok(Err(text))
+    }
+}
+
+/// The first lines of the first error in `output`, or of `output` if there is no error line
+fn error_excerpt(output: &str) -> String {
+    let lines = output.lines().collect::<Vec<_>>();
+    let start = lines
+        .iter()
+        .position(|l| l.starts_with("error"))
+        .unwrap_or(0);
+    lines[start..]
+        .iter()
+        .take(EXCERPT_LINES)
+        .map(|l| format!("  {l}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn excerpt_starts_at_first_error() {
+        let output = "\
+    Checking foo v0.1.0
+error[E0425]: cannot find function `bar` in this scope
+ --> src/lib.rs:1:13
+  |
+1 | fn foo() { bar() }
+  |            ^^^ not found in this scope
+";
+        let actual = error_excerpt(output);
+        let expected = "  error[E0425]: cannot find function `bar` in this scope
+   --> src/lib.rs:1:13
+    |
+  1 | fn foo() { bar() }
+    |            ^^^ not found in this scope";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn excerpt_without_error_line() {
+        let output = "something went wrong\n";
+        assert_eq!(error_excerpt(output), "  something went wrong");
+    }
+}
@@ -1,6 +1,6 @@
 use super::Dependency;
+use super::PrereleasePolicy;
 use super::RegistrySource;
-use super::VersionExt;
 
 /// Simplified represetation of `package.rust-version`
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -54,6 +54,16 @@ impl std::str::FromStr for RustVersion {
     }
 }
 
+impl std::fmt::Display for RustVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+        if self.patch != 0 {
+            write!(f, ".{}", self.patch)?;
+        }
+        Ok(())
+    }
+}
+
 impl From<&'_ semver::VersionReq> for RustVersion {
     fn from(version_req: &semver::VersionReq) -> Self {
         // HACK: `rust-version` is a subset of the `VersionReq` syntax that only ever
@@ -82,32 +92,16 @@ impl From<&'_ semver::Version> for RustVersion {
     }
 }
 
-// Checks whether a version object is a stable release
-fn version_is_stable(version: &semver::Version) -> bool {
-    !version.is_prerelease()
-}
-
-/// Read latest version from Versions structure
+/// Find the newest version, among the pre-releases `prerelease` allows for a requirement currently
+/// on the `current` pre-release
 pub fn find_latest_version(
     versions: &[tame_index::IndexVersion],
-    flag_allow_prerelease: bool,
+    prerelease: PrereleasePolicy,
+    current: &semver::Prerelease,
     rust_version: Option<RustVersion>,
 ) -> Option<Dependency> {
-    let (latest, _) = versions
-        .iter()
-        .filter_map(|k| Some((k, k.version.parse::<semver::Version>().ok()?)))
-        .filter(|(_, v)| flag_allow_prerelease || version_is_stable(v))
-        .filter(|(k, _)| !k.yanked)
-        .filter(|(k, _)| {
-            rust_version
-                .and_then(|rust_version| {
-                    k.rust_version
-                        .as_ref()
-                        .and_then(|k_rust_version| k_rust_version.parse::<RustVersion>().ok())
-                        .map(|k_rust_version| k_rust_version <= rust_version)
-                })
-                .unwrap_or(true)
-        })
+    let (latest, _) = installable_versions(versions, rust_version)
+        .filter(|(_, v)| prerelease.allows(current, v))
         .max_by_key(|(_, v)| v.clone())?;
 
     let name = &latest.name;
@@ -115,17 +109,49 @@ pub fn find_latest_version(
     Some(Dependency::new(name).set_source(RegistrySource::new(version)))
 }
 
+/// Find the newest version matching `version_req`, among the pre-releases `prerelease` allows for
+/// a requirement currently on the `current` pre-release
 pub fn find_compatible_version(
     versions: &[tame_index::IndexVersion],
     version_req: &semver::VersionReq,
+    prerelease: PrereleasePolicy,
+    current: &semver::Prerelease,
     rust_version: Option<RustVersion>,
 ) -> Option<Dependency> {
-    let (latest, _) = versions
+    let (latest, _) = installable_versions(versions, rust_version)
+        .filter(|(_, v)| version_req.matches(v) && prerelease.allows(current, v))
+        .max_by_key(|(_, v)| v.clone())?;
+
+    let name = &latest.name;
+    let version = latest.version.to_string();
+    Some(Dependency::new(name).set_source(RegistrySource::new(version)))
+}
+
+/// Find the oldest version `is_candidate` accepts
+pub fn find_minimal_version(
+    versions: &[tame_index::IndexVersion],
+    is_candidate: impl Fn(&semver::Version) -> bool,
+    rust_version: Option<RustVersion>,
+) -> Option<Dependency> {
+    let (minimal, _) = installable_versions(versions, rust_version)
+        .filter(|(_, v)| is_candidate(v))
+        .min_by_key(|(_, v)| v.clone())?;
+
+    let name = &minimal.name;
+    let version = minimal.version.to_string();
+    Some(Dependency::new(name).set_source(RegistrySource::new(version)))
+}
+
+/// Versions that are neither yanked nor too new for `rust_version`
+pub fn installable_versions(
+    versions: &[tame_index::IndexVersion],
+    rust_version: Option<RustVersion>,
+) -> impl Iterator<Item = (&tame_index::IndexVersion, semver::Version)> {
+    versions
         .iter()
         .filter_map(|k| Some((k, k.version.parse::<semver::Version>().ok()?)))
-        .filter(|(_, v)| version_req.matches(v))
         .filter(|(k, _)| !k.yanked)
-        .filter(|(k, _)| {
+        .filter(move |(k, _)| {
             rust_version
                 .and_then(|rust_version| {
                     k.rust_version
@@ -135,9 +161,4 @@ pub fn find_compatible_version(
                 })
                 .unwrap_or(true)
         })
-        .max_by_key(|(_, v)| v.clone())?;
-
-    let name = &latest.name;
-    let version = latest.version.to_string();
-    Some(Dependency::new(name).set_source(RegistrySource::new(version)))
 }
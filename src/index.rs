@@ -14,6 +14,11 @@ pub enum CertsSource {
     Native,
 }
 
+/// When each version of a crate was published, as recorded by the `pubtime` of its index entries
+///
+/// Registries leave it out for versions published before they started recording it.
+pub type PublishTimes = std::collections::HashMap<String, String>;
+
 pub struct IndexCache {
     certs_source: CertsSource,
     index: std::collections::HashMap<Url, AnyIndexCache>,
@@ -63,6 +68,13 @@ impl IndexCache {
             .krate(name)
     }
 
+    /// When each version of `name` was published, for the versions the registry knows it of
+    pub fn publish_times(&mut self, registry: &Url, name: &str) -> CargoResult<PublishTimes> {
+        self.index(registry)
+            .with_context(|| format!("failed to look up {name}"))?
+            .publish_times(name)
+    }
+
     fn index<'s>(&'s mut self, registry: &Url) -> CargoResult<&'s mut AnyIndexCache> {
         if !self.index.contains_key(registry) {
             let index = AnyIndex::open(registry, self.certs_source)?;
@@ -76,6 +88,7 @@ impl IndexCache {
 struct AnyIndexCache {
     index: AnyIndex,
     cache: std::collections::HashMap<String, Option<IndexKrate>>,
+    publish_times: std::collections::HashMap<String, PublishTimes>,
 }
 
 impl AnyIndexCache {
@@ -84,6 +97,7 @@ impl AnyIndexCache {
         Self {
             index,
             cache: std::collections::HashMap::new(),
+            publish_times: std::collections::HashMap::new(),
         }
     }
 
@@ -110,10 +124,19 @@ impl AnyIndexCache {
             return Ok(entry.clone());
         }
 
-        let entry = self.index.krate(name)?;
+        let (entry, publish_times) = self.index.krate(name)?;
         self.cache.insert(name.to_owned(), entry.clone());
+        // A registry answering that the entry is unchanged sends no versions to read them from
+        if !publish_times.is_empty() {
+            self.publish_times.insert(name.to_owned(), publish_times);
+        }
         Ok(entry)
     }
+
+    fn publish_times(&mut self, name: &str) -> CargoResult<PublishTimes> {
+        self.krate(name)?;
+        Ok(self.publish_times.get(name).cloned().unwrap_or_default())
+    }
 }
 
 enum AnyIndex {
@@ -134,7 +157,7 @@ impl AnyIndex {
         }
     }
 
-    fn krate(&mut self, name: &str) -> CargoResult<Option<IndexKrate>> {
+    fn krate(&mut self, name: &str) -> CargoResult<(Option<IndexKrate>, PublishTimes)> {
         match self {
             Self::Local(index) => index.krate(name),
             Self::Remote(index) => index.krate(name),
@@ -158,7 +181,7 @@ impl LocalIndex {
         Ok(Self { index, root: path })
     }
 
-    fn krate(&mut self, name: &str) -> CargoResult<Option<IndexKrate>> {
+    fn krate(&mut self, name: &str) -> CargoResult<(Option<IndexKrate>, PublishTimes)> {
         let name = tame_index::KrateName::cargo(name)?;
         // HACK: for some reason, `tame_index` puts `index` in the middle
         let entry_path = self.index.krate_path(name);
@@ -170,10 +193,10 @@ impl LocalIndex {
             .map_err(|_err| anyhow::format_err!("invalid index path {entry_path:?}"))?;
         let entry_path = self.root.join(rel_path);
         let Ok(entry) = std::fs::read(&entry_path) else {
-            return Ok(None);
+            return Ok((None, PublishTimes::new()));
         };
         let results = IndexKrate::from_slice(&entry)?;
-        Ok(Some(results))
+        Ok((Some(results), read_publish_times(&entry)))
     }
 }
 
@@ -212,7 +235,7 @@ impl RemoteIndex {
         })
     }
 
-    fn krate(&mut self, name: &str) -> CargoResult<Option<IndexKrate>> {
+    fn krate(&mut self, name: &str) -> CargoResult<(Option<IndexKrate>, PublishTimes)> {
         log::trace!("krate {name}");
         let etag = self
             .etags
@@ -262,12 +285,29 @@ impl RemoteIndex {
             .extend(res.headers().iter().map(|(k, v)| (k.clone(), v.clone())));
 
         let body = res.bytes()?;
+        let publish_times = read_publish_times(&body);
         let response = builder
             .body(body.to_vec())
             .map_err(|e| tame_index::Error::from(tame_index::error::HttpError::from(e)))?;
 
-        self.index
-            .parse_remote_response(krate_name, response, false, &self.lock)
-            .map_err(Into::into)
+        let krate = self
+            .index
+            .parse_remote_response(krate_name, response, false, &self.lock)?;
+        Ok((krate, publish_times))
+    }
+}
+
+/// The `pubtime` of each version in an index entry, one JSON object per line
+fn read_publish_times(entry: &[u8]) -> PublishTimes {
+    #[derive(serde_derive::Deserialize)]
+    struct Published {
+        vers: String,
+        pubtime: Option<String>,
     }
+
+    entry
+        .split(|b| *b == b'\n')
+        .filter_map(|line| serde_json::from_slice::<Published>(line).ok())
+        .filter_map(|published| Some((published.vers, published.pubtime?)))
+        .collect()
 }
@@ -0,0 +1,162 @@
+//! Record of the files changed by the last edit session, for undoing it
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::errors::{CargoResult, Context};
+use super::script::is_script;
+use super::transaction::write_atomic;
+use super::util::{shell_note, shell_status};
+
+const JOURNAL_DIR: &str = "cargo-edit";
+const JOURNAL_FILE: &str = "journal.toml";
+
+/// Original and final contents of every file touched by an edit session.
+///
+/// Saved under `target/cargo-edit/` so the session can be reverted with [`EditJournal::undo`],
+/// independent of any other changes in the working tree.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EditJournal {
+    command: String,
+    #[serde(default, rename = "file")]
+    files: Vec<JournalFile>,
+    #[serde(skip)]
+    target_dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalFile {
+    path: PathBuf,
+    /// Contents before the session, `None` if the file did not exist
+    original: Option<String>,
+    /// Contents after the session, `None` if the file does not exist
+    edited: Option<String>,
+}
+
+impl EditJournal {
+    /// Start a journal for `command`, to be saved in `target_dir`
+    pub fn new(target_dir: &Path, command: &str) -> Self {
+        Self {
+            command: command.to_owned(),
+            files: Vec::new(),
+            target_dir: target_dir.to_owned(),
+        }
+    }
+
+    /// Remember the current contents of `path`, before it gets modified.
+    ///
+    /// Only the first call for a given `path` has an effect.
+    pub fn record(&mut self, path: &Path) -> CargoResult<()> {
+        if self.files.iter().any(|f| f.path == path) {
+            return Ok(());
+        }
+        let original = read_if_exists(path)?;
+        self.files.push(JournalFile {
+            path: path.to_owned(),
+            original,
+            edited: None,
+        });
+        Ok(())
+    }
+
+    /// Capture the current contents of the recorded files and save the journal, replacing the
+    /// one from the previous session.
+    ///
+    /// This can be called again as the session makes further changes.
+    pub fn save(&mut self) -> CargoResult<()> {
+        for file in &mut self.files {
+            file.edited = read_if_exists(&file.path)?;
+        }
+        if self.files.iter().all(|f| f.original == f.edited) {
+            return Ok(());
+        }
+
+        let dir = self.target_dir.join(JOURNAL_DIR);
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let content = toml::to_string(&self).context("Failed to serialize edit journal")?;
+        write_atomic(&dir.join(JOURNAL_FILE), content.as_bytes())
+    }
+
+    /// Restore the files changed by the last saved session.
+    ///
+    /// Nothing is restored if any of those files changed after the session.
+    ///
+    /// Returns the command that made the edits and the restored paths.
+    pub fn undo(target_dir: &Path) -> CargoResult<(String, Vec<PathBuf>)> {
+        let journal_path = target_dir.join(JOURNAL_DIR).join(JOURNAL_FILE);
+        let content = match fs::read_to_string(&journal_path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                anyhow::bail!("no edit session to undo");
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read {}", journal_path.display()));
+            }
+        };
+        let journal = toml::from_str::<Self>(&content)
+            .with_context(|| format!("invalid edit journal {}", journal_path.display()))?;
+
+        let changed = journal
+            .files
+            .iter()
+            .map(|f| Ok((f, read_if_exists(&f.path)? != f.edited)))
+            .collect::<CargoResult<Vec<_>>>()?
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(f, _)| f.path.display().to_string())
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            anyhow::bail!(
+                "cannot undo `{}`, files were modified since:\n  {}",
+                journal.command,
+                changed.join("\n  ")
+            );
+        }
+
+        for file in journal.files.iter().filter(|f| f.original != f.edited) {
+            match &file.original {
+                Some(original) => write_atomic(&file.path, original.as_bytes())?,
+                None => fs::remove_file(&file.path)
+                    .with_context(|| format!("Failed to remove {}", file.path.display()))?,
+            }
+        }
+        fs::remove_file(&journal_path)
+            .with_context(|| format!("Failed to remove {}", journal_path.display()))?;
+
+        let restored = journal
+            .files
+            .into_iter()
+            .filter(|f| f.original != f.edited)
+            .map(|f| f.path)
+            .collect();
+        Ok((journal.command, restored))
+    }
+}
+
+/// Undo the last edit session of the workspace at `manifest_path`, reporting the restored files
+pub fn undo_last_run(manifest_path: Option<&Path>) -> CargoResult<()> {
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    if let Some(manifest_path) = manifest_path {
+        cmd.manifest_path(manifest_path);
+        if is_script(manifest_path) {
+            cmd.other_options(vec!["-Zscript".to_owned()]);
+        }
+    }
+    cmd.no_deps();
+    let metadata = cmd.exec()?;
+
+    let (command, restored) = EditJournal::undo(metadata.target_directory.as_std_path())?;
+    for path in restored {
+        shell_status("Restored", &path.display().to_string())?;
+    }
+    shell_note(&format!("Undid the last `{command}`"))?;
+    Ok(())
+}
+
+fn read_if_exists(path: &Path) -> CargoResult<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
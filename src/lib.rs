@@ -26,9 +26,12 @@ mod dependency;
 mod errors;
 mod fetch;
 mod index;
+mod journal;
 mod manifest;
 mod metadata;
 mod registry;
+mod script;
+mod transaction;
 mod util;
 mod version;
 
@@ -40,13 +43,21 @@ pub use dependency::RegistrySource;
 pub use dependency::Source;
 pub use dependency::WorkspaceSource;
 pub use errors::*;
-pub use fetch::{RustVersion, find_compatible_version, find_latest_version};
+pub use fetch::{
+    RustVersion, find_compatible_version, find_latest_version, find_minimal_version,
+    installable_versions,
+};
 pub use index::*;
-pub use manifest::{LocalManifest, Manifest, find, get_dep_version, set_dep_version};
+pub use journal::{EditJournal, undo_last_run};
+pub use manifest::{
+    DepKind, DepTable, LocalManifest, Manifest, find, get_dep_version, set_dep_tag, set_dep_version,
+};
 pub use metadata::manifest_from_pkgid;
 pub use registry::registry_url;
+pub use script::{is_script, root_manifest_path};
+pub use transaction::{ManifestTransaction, write_atomic};
 pub use util::{
     Color, ColorChoice, colorize_stderr, shell_note, shell_print, shell_status, shell_warn,
-    shell_write_stderr, shell_write_stdout,
+    shell_write_diff, shell_write_stderr, shell_write_stdout,
 };
-pub use version::{VersionExt, upgrade_requirement};
+pub use version::{PrereleasePolicy, VersionExt, upgrade_requirement};
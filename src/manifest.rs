@@ -5,19 +5,53 @@ use std::{env, str};
 
 use semver::Version;
 
+use super::RustVersion;
 use super::errors::{CargoResult, Context, non_existent_dependency_err, non_existent_table_err};
 use super::metadata::find_manifest_path;
+use super::script::{self, is_script};
+use super::transaction::write_atomic;
+use super::util::shell_write_diff;
 
+/// Kind of dependency, by the table it is declared in
 #[derive(PartialEq, Eq, Hash, Ord, PartialOrd, Clone, Debug, Copy)]
-pub(crate) enum DepKind {
+pub enum DepKind {
+    /// `[dependencies]`
     Normal,
+    /// `[dev-dependencies]`
     Development,
+    /// `[build-dependencies]`
     Build,
 }
 
+impl DepKind {
+    /// Short name, as accepted by [`DepKind::from_str`][str::FromStr]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Development => "dev",
+            Self::Build => "build",
+        }
+    }
+}
+
+impl str::FromStr for DepKind {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "normal" => Ok(Self::Normal),
+            "dev" => Ok(Self::Development),
+            "build" => Ok(Self::Build),
+            _ => anyhow::bail!(
+                "unsupported dependency kind `{text}`, expected `normal`, `dev` or `build`"
+            ),
+        }
+    }
+}
+
 /// Dependency table to add dep to
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub(crate) struct DepTable {
+pub struct DepTable {
     kind: DepKind,
     target: Option<String>,
 }
@@ -49,6 +83,37 @@ impl DepTable {
         self
     }
 
+    /// The table at `table_path`, like those from [`Manifest::get_dependency_table_paths`]
+    ///
+    /// `[workspace.dependencies]` is of no kind until inherited, so it has no `DepTable`.
+    pub fn from_path(table_path: &[String]) -> Option<Self> {
+        let (target, kind_table) = match table_path {
+            [kind_table] => (None, kind_table),
+            [target_key, target, kind_table] if target_key == "target" => {
+                (Some(target), kind_table)
+            }
+            _ => return None,
+        };
+        let table = Self::KINDS
+            .iter()
+            .find(|kind| kind.kind_table() == kind_table)?
+            .clone();
+        Some(match target {
+            Some(target) => table.set_target(target),
+            None => table,
+        })
+    }
+
+    /// Kind of the dependencies in the table
+    pub fn kind(&self) -> DepKind {
+        self.kind
+    }
+
+    /// Platform the dependencies are limited to, like `cfg(windows)`
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
     fn kind_table(&self) -> &str {
         match self.kind {
             DepKind::Normal => "dependencies",
@@ -58,6 +123,15 @@ impl DepTable {
     }
 }
 
+impl std::fmt::Display for DepTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.target {
+            Some(target) => write!(f, "{} ({target})", self.kind.as_str()),
+            None => self.kind.as_str().fmt(f),
+        }
+    }
+}
+
 impl Default for DepTable {
     fn default() -> Self {
         Self::new()
@@ -82,16 +156,75 @@ impl Manifest {
     ///
     /// If there is no table at the specified path, then a non-existent table
     /// error will be returned.
-    pub(crate) fn get_table_mut<'a>(
+    pub fn get_table_mut<'a>(
         &'a mut self,
         table_path: &[String],
     ) -> CargoResult<&'a mut toml_edit::Item> {
         self.get_table_mut_internal(table_path, false)
     }
 
+    /// Get the paths of all tables that might contain dependencies, including
+    /// `[workspace.dependencies]`.
+    ///
+    /// These are visited in the same order as [`LocalManifest::get_dependency_tables_mut`].
+    pub fn get_dependency_table_paths(&self) -> Vec<Vec<String>> {
+        let is_kind_table = |key: &str| DepTable::KINDS.iter().any(|kind| kind.kind_table() == key);
+
+        let mut paths = Vec::new();
+        for (key, item) in self.data.iter() {
+            if is_kind_table(key) {
+                if item.is_table_like() {
+                    paths.push(vec![key.to_owned()]);
+                }
+            } else if key == "workspace" {
+                if item
+                    .get("dependencies")
+                    .map(|t| t.is_table_like())
+                    .unwrap_or(false)
+                {
+                    paths.push(vec![key.to_owned(), "dependencies".to_owned()]);
+                }
+            } else if key == "target" {
+                for (target, target_table) in
+                    item.as_table_like().into_iter().flat_map(|t| t.iter())
+                {
+                    for (kind, table) in target_table
+                        .as_table_like()
+                        .into_iter()
+                        .flat_map(|t| t.iter())
+                    {
+                        if is_kind_table(kind) && table.is_table_like() {
+                            paths.push(vec![key.to_owned(), target.to_owned(), kind.to_owned()]);
+                        }
+                    }
+                }
+            }
+        }
+        paths
+    }
+
+    /// Get the paths to the `[patch.<source>]` tables and the `[replace]` table, in file order.
+    ///
+    /// These only take effect in the workspace root manifest.
+    pub fn get_patch_table_paths(&self) -> Vec<Vec<String>> {
+        let mut paths = Vec::new();
+        for (key, item) in self.data.iter() {
+            if key == "patch" {
+                for (source, table) in item.as_table_like().into_iter().flat_map(|t| t.iter()) {
+                    if table.is_table_like() {
+                        paths.push(vec![key.to_owned(), source.to_owned()]);
+                    }
+                }
+            } else if key == "replace" && item.is_table_like() {
+                paths.push(vec![key.to_owned()]);
+            }
+        }
+        paths
+    }
+
     /// Get all sections in the manifest that exist and might contain dependencies.
     /// The returned items are always `Table` or `InlineTable`.
-    pub(crate) fn get_sections(&self) -> Vec<(DepTable, toml_edit::Item)> {
+    pub fn get_sections(&self) -> Vec<(DepTable, toml_edit::Item)> {
         let mut sections = Vec::new();
 
         for table in DepTable::KINDS {
@@ -219,7 +352,15 @@ impl LocalManifest {
             anyhow::bail!("can only edit absolute paths, got {}", path.display());
         }
         let data = fs::read_to_string(path).with_context(|| "Failed to read manifest contents")?;
-        let manifest = data.parse().context("Unable to parse Cargo.toml")?;
+        let manifest = if is_script(path) {
+            let embedded = script::extract(&data)
+                .with_context(|| format!("Unable to find the manifest in {}", path.display()))?;
+            embedded
+                .parse()
+                .context("Unable to parse embedded manifest")?
+        } else {
+            data.parse().context("Unable to parse Cargo.toml")?
+        };
         Ok(LocalManifest {
             manifest,
             path: path.to_owned(),
@@ -228,10 +369,42 @@ impl LocalManifest {
 
     /// Write changes back to the file
     pub fn write(&self) -> CargoResult<()> {
-        let s = self.manifest.data.to_string();
+        let s = self.file_contents()?;
         let new_contents_bytes = s.as_bytes();
 
-        fs::write(&self.path, new_contents_bytes).context("Failed to write updated Cargo.toml")
+        write_atomic(&self.path, new_contents_bytes).context("Failed to write updated Cargo.toml")
+    }
+
+    /// Print the unwritten changes as a unified diff against the file on disk
+    pub fn write_diff(&self) -> CargoResult<()> {
+        let original =
+            fs::read_to_string(&self.path).with_context(|| "Failed to read manifest contents")?;
+        let modified = self.render(&original)?;
+        shell_write_diff(&self.path, &original, &modified)
+    }
+
+    /// The contents the file would have with the unwritten changes.
+    ///
+    /// For cargo scripts, the manifest is spliced back into the script on disk.
+    pub fn file_contents(&self) -> CargoResult<String> {
+        if is_script(&self.path) {
+            let original = fs::read_to_string(&self.path)
+                .with_context(|| "Failed to read manifest contents")?;
+            self.render(&original)
+        } else {
+            Ok(self.manifest.data.to_string())
+        }
+    }
+
+    /// The contents of a file currently holding `original`, with the changes applied
+    pub(crate) fn render(&self, original: &str) -> CargoResult<String> {
+        let data = self.manifest.data.to_string();
+        if is_script(&self.path) {
+            script::splice(original, &data)
+                .with_context(|| format!("Unable to find the manifest in {}", self.path.display()))
+        } else {
+            Ok(data)
+        }
     }
 
     /// Remove entry from a Cargo.toml.
@@ -331,6 +504,12 @@ impl LocalManifest {
 
     /// Override the manifest's version
     pub fn set_package_version(&mut self, version: &Version) {
+        if !self.data.contains_key("package") {
+            // Cargo scripts may leave out `[package]`, keep it at the top like `cargo new` does
+            let mut package = toml_edit::Table::new();
+            package.set_position(Some(-1));
+            self.data.insert("package", toml_edit::Item::Table(package));
+        }
         self.data["package"]["version"] = toml_edit::value(version.to_string());
     }
 
@@ -363,6 +542,43 @@ impl LocalManifest {
         self.data["workspace"]["package"]["version"] = toml_edit::value(version.to_string());
     }
 
+    /// `true` if the package inherits the workspace `rust-version`
+    pub fn rust_version_is_inherited(&self) -> bool {
+        self.data
+            .get("package")
+            .and_then(|p| p.get("rust-version"))
+            .and_then(|r| r.get("workspace"))
+            .and_then(|w| w.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Override the package's `rust-version`, keeping its formatting
+    pub fn set_package_rust_version(&mut self, rust_version: &RustVersion) {
+        overwrite_value(
+            &mut self.data["package"]["rust-version"],
+            rust_version.to_string(),
+        );
+    }
+
+    /// Get the current workspace `rust-version`, if any.
+    pub fn get_workspace_rust_version(&self) -> Option<RustVersion> {
+        self.data
+            .get("workspace")?
+            .get("package")?
+            .get("rust-version")?
+            .as_str()?
+            .parse()
+            .ok()
+    }
+
+    /// Override the workspace's `rust-version`, keeping its formatting
+    pub fn set_workspace_rust_version(&mut self, rust_version: &RustVersion) {
+        overwrite_value(
+            &mut self.data["workspace"]["package"]["rust-version"],
+            rust_version.to_string(),
+        );
+    }
+
     /// Remove references to `dep_key` if its no longer present
     pub fn gc_dep(&mut self, dep_key: &str) {
         let status = self.dep_feature(dep_key);
@@ -491,6 +707,16 @@ pub fn set_dep_version(dep_item: &mut toml_edit::Item, new_version: &str) -> Car
     Ok(())
 }
 
+/// Set the git tag a dependency entry is pinned to
+pub fn set_dep_tag(dep_item: &mut toml_edit::Item, new_tag: &str) -> CargoResult<()> {
+    let tag = dep_item
+        .as_table_like_mut()
+        .and_then(|t| t.get_mut("tag"))
+        .ok_or_else(|| anyhow::format_err!("Missing tag field"))?;
+    overwrite_value(tag, new_tag);
+    Ok(())
+}
+
 /// Overwrite a value while preserving the original formatting
 fn overwrite_value(item: &mut toml_edit::Item, value: impl Into<toml_edit::Value>) {
     let mut value = value.into();
@@ -508,3 +734,59 @@ fn overwrite_value(item: &mut toml_edit::Item, value: impl Into<toml_edit::Value
 pub(crate) fn str_or_1_len_table(item: &toml_edit::Item) -> bool {
     item.is_str() || item.as_table_like().map(|t| t.len() == 1).unwrap_or(false)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(path: &[&str]) -> Vec<String> {
+        path.iter().map(|s| (*s).to_owned()).collect()
+    }
+
+    #[test]
+    fn dep_table_from_path() {
+        let table = DepTable::from_path(&path(&["dev-dependencies"])).unwrap();
+        assert_eq!(table.kind(), DepKind::Development);
+        assert_eq!(table.target(), None);
+        assert_eq!(table.to_string(), "dev");
+
+        let table =
+            DepTable::from_path(&path(&["target", "cfg(windows)", "build-dependencies"])).unwrap();
+        assert_eq!(table.kind(), DepKind::Build);
+        assert_eq!(table.target(), Some("cfg(windows)"));
+        assert_eq!(table.to_string(), "build (cfg(windows))");
+
+        assert_eq!(
+            DepTable::from_path(&path(&["workspace", "dependencies"])),
+            None
+        );
+        assert_eq!(DepTable::from_path(&path(&["patch", "crates-io"])), None);
+    }
+
+    #[test]
+    fn dep_kind_from_str() {
+        for kind in [DepKind::Normal, DepKind::Development, DepKind::Build] {
+            assert_eq!(kind.as_str().parse::<DepKind>().unwrap(), kind);
+        }
+        assert!("development".parse::<DepKind>().is_err());
+    }
+
+    #[test]
+    fn raise_workspace_rust_version() {
+        let mut manifest = LocalManifest {
+            path: PathBuf::from("Cargo.toml"),
+            manifest: "[workspace.package]\nrust-version = \"1.60\" # CI\n"
+                .parse()
+                .unwrap(),
+        };
+        assert_eq!(
+            manifest.get_workspace_rust_version(),
+            Some("1.60".parse().unwrap())
+        );
+        manifest.set_workspace_rust_version(&"1.85.1".parse().unwrap());
+        assert_eq!(
+            manifest.to_string(),
+            "[workspace.package]\nrust-version = \"1.85.1\" # CI\n"
+        );
+    }
+}
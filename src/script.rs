@@ -0,0 +1,175 @@
+//! Manifests embedded in single-file cargo scripts
+//!
+//! A script's manifest lives in a frontmatter block at the top of the `.rs` file:
+//!
+//! ```text
+//! #!/usr/bin/env cargo
+//! ---cargo
+//! [dependencies]
+//! clap = "4"
+//! ---
+//!
+//! fn main() {}
+//! ```
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use super::errors::CargoResult;
+
+const FENCE: &str = "---";
+const INFOSTRING: &str = "cargo";
+
+/// Whether `path` points to a single-file cargo script, rather than a `Cargo.toml`
+pub fn is_script(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "rs")
+}
+
+/// The manifest at the root of the workspace, which for a cargo script is the script itself
+pub fn root_manifest_path(
+    metadata: &cargo_metadata::Metadata,
+    manifest_path: Option<&Path>,
+) -> PathBuf {
+    if manifest_path.is_some_and(is_script)
+        && let Some(script) = metadata
+            .packages
+            .iter()
+            .find(|p| metadata.workspace_members.contains(&p.id))
+    {
+        return script.manifest_path.as_std_path().to_owned();
+    }
+    metadata.workspace_root.as_std_path().join("Cargo.toml")
+}
+
+/// The manifest embedded in `source`, empty if the script has no frontmatter
+pub(crate) fn extract(source: &str) -> CargoResult<&str> {
+    Ok(find_manifest(source)?
+        .map(|range| &source[range])
+        .unwrap_or_default())
+}
+
+/// Replace the manifest embedded in `source` with `manifest`, leaving the rest of the script
+/// untouched
+pub(crate) fn splice(source: &str, manifest: &str) -> CargoResult<String> {
+    match find_manifest(source)? {
+        Some(range) => {
+            let mut spliced = String::with_capacity(source.len() + manifest.len());
+            spliced.push_str(&source[..range.start]);
+            spliced.push_str(manifest);
+            spliced.push_str(&source[range.end..]);
+            Ok(spliced)
+        }
+        None if manifest.trim().is_empty() => Ok(source.to_owned()),
+        None => {
+            let start = shebang_len(source);
+            let newline = if manifest.ends_with('\n') { "" } else { "\n" };
+            Ok(format!(
+                "{}{FENCE}{INFOSTRING}\n{manifest}{newline}{FENCE}\n{}",
+                &source[..start],
+                &source[start..]
+            ))
+        }
+    }
+}
+
+/// Byte range of the manifest between the frontmatter fences
+fn find_manifest(source: &str) -> CargoResult<Option<Range<usize>>> {
+    let mut offset = shebang_len(source);
+    for line in source[offset..].split_inclusive('\n') {
+        if !line.trim().is_empty() {
+            break;
+        }
+        offset += line.len();
+    }
+
+    let rest = &source[offset..];
+    let dashes = rest.len() - rest.trim_start_matches('-').len();
+    if dashes < FENCE.len() {
+        return Ok(None);
+    }
+    let fence = &rest[..dashes];
+    let Some(open_len) = rest.find('\n').map(|i| i + 1) else {
+        anyhow::bail!("unclosed frontmatter, expected a closing `{fence}`");
+    };
+    let infostring = rest[dashes..open_len].trim();
+    if !infostring.is_empty() && infostring != INFOSTRING {
+        anyhow::bail!("unsupported frontmatter `{infostring}`, expected `{INFOSTRING}`");
+    }
+
+    let start = offset + open_len;
+    let mut end = start;
+    for line in source[start..].split_inclusive('\n') {
+        if let Some(after) = line.strip_prefix(fence)
+            && after.trim().is_empty()
+        {
+            return Ok(Some(start..end));
+        }
+        end += line.len();
+    }
+    anyhow::bail!("unclosed frontmatter, expected a closing `{fence}`")
+}
+
+/// Length of the `#!` line, if any
+///
+/// `#![` starts an inner attribute, not a shebang.
+fn shebang_len(source: &str) -> usize {
+    match source.strip_prefix("#!") {
+        Some(rest) if !rest.trim_start().starts_with('[') => {
+            source.find('\n').map(|i| i + 1).unwrap_or(source.len())
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCRIPT: &str =
+        "#!/usr/bin/env cargo\n---cargo\n[dependencies]\nclap = \"4\" # cli\n---\n\nfn main() {}\n";
+
+    #[test]
+    fn extract_manifest() {
+        assert_eq!(
+            extract(SCRIPT).unwrap(),
+            "[dependencies]\nclap = \"4\" # cli\n"
+        );
+        assert_eq!(extract("#![allow(dead_code)]\nfn main() {}\n").unwrap(), "");
+    }
+
+    #[test]
+    fn splice_keeps_script() {
+        let actual = splice(SCRIPT, "[dependencies]\nclap = \"4.5\" # cli\n").unwrap();
+        assert_eq!(
+            actual,
+            "#!/usr/bin/env cargo\n---cargo\n[dependencies]\nclap = \"4.5\" # cli\n---\n\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn splice_adds_frontmatter() {
+        let actual = splice(
+            "#!/usr/bin/env cargo\nfn main() {}\n",
+            "[package]\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            actual,
+            "#!/usr/bin/env cargo\n---cargo\n[package]\nversion = \"0.1.0\"\n---\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn longer_fences() {
+        let script = "----\n[dependencies]\nfoo = \"1\"\n---\nbar = \"2\"\n----\nfn main() {}\n";
+        assert_eq!(
+            extract(script).unwrap(),
+            "[dependencies]\nfoo = \"1\"\n---\nbar = \"2\"\n"
+        );
+    }
+
+    #[test]
+    fn unsupported_infostring() {
+        assert!(extract("---toml\n---\n").is_err());
+        assert!(extract("---cargo\n[dependencies]\n").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! Write edits to several manifests as one unit
+use std::fs;
+use std::io::Write as _;
+use std::path::Path;
+
+use super::errors::{CargoResult, Context};
+use super::manifest::LocalManifest;
+
+/// Staged edits to multiple manifests that are written together, or not at all.
+///
+/// Each file is replaced atomically, via a temporary file and a rename, so a manifest is never
+/// left partially written.  If any write, or the validation run after writing, fails then every
+/// manifest is restored to its original contents.
+#[derive(Debug, Default)]
+pub struct ManifestTransaction {
+    staged: Vec<Staged>,
+}
+
+#[derive(Debug)]
+struct Staged {
+    manifest: LocalManifest,
+    original: Vec<u8>,
+}
+
+impl ManifestTransaction {
+    /// Start an empty transaction
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stage an edited manifest, replacing any edits already staged for the same file.
+    pub fn stage(&mut self, manifest: LocalManifest) -> CargoResult<()> {
+        if let Some(staged) = self
+            .staged
+            .iter_mut()
+            .find(|s| s.manifest.path == manifest.path)
+        {
+            staged.manifest = manifest;
+        } else {
+            let original =
+                fs::read(&manifest.path).with_context(|| "Failed to read manifest contents")?;
+            self.staged.push(Staged { manifest, original });
+        }
+        Ok(())
+    }
+
+    /// Get the staged manifest at `path`, loading it from disk if it isn't staged yet.
+    ///
+    /// Later edits build on the ones already staged for the same file.
+    pub fn manifest_mut(&mut self, path: &Path) -> CargoResult<&mut LocalManifest> {
+        let index = match self.staged.iter().position(|s| s.manifest.path == path) {
+            Some(index) => index,
+            None => {
+                let manifest = LocalManifest::try_new(path)?;
+                self.stage(manifest)?;
+                self.staged.len() - 1
+            }
+        };
+        Ok(&mut self.staged[index].manifest)
+    }
+
+    /// Staged manifests that differ from what is on disk
+    pub fn modified(&self) -> impl Iterator<Item = &LocalManifest> + '_ {
+        self.staged
+            .iter()
+            .filter(|s| s.is_modified())
+            .map(|s| &s.manifest)
+    }
+
+    /// Whether any staged manifest differs from what is on disk
+    pub fn is_modified(&self) -> bool {
+        self.modified().next().is_some()
+    }
+
+    /// Write all modified manifests, then check the result with `validate`.
+    ///
+    /// On failure, every manifest that was written is restored.
+    pub fn commit<F>(self, validate: F) -> CargoResult<()>
+    where
+        F: FnOnce() -> CargoResult<()>,
+    {
+        let modified = self
+            .staged
+            .iter()
+            .filter(|s| s.is_modified())
+            .collect::<Vec<_>>();
+
+        let mut written = Vec::new();
+        for staged in &modified {
+            let result = staged
+                .render()
+                .and_then(|contents| write_atomic(&staged.manifest.path, contents.as_bytes()));
+            if let Err(err) = result {
+                rollback(&written);
+                return Err(err).context("rolled back manifest changes");
+            }
+            written.push(*staged);
+        }
+
+        if let Err(err) = validate() {
+            rollback(&written);
+            return Err(err).context("rolled back manifest changes");
+        }
+
+        Ok(())
+    }
+}
+
+impl Staged {
+    fn render(&self) -> CargoResult<String> {
+        self.manifest
+            .render(&String::from_utf8_lossy(&self.original))
+    }
+
+    fn is_modified(&self) -> bool {
+        self.render()
+            .map(|contents| contents.as_bytes() != self.original.as_slice())
+            .unwrap_or(true)
+    }
+}
+
+fn rollback(written: &[&Staged]) {
+    for staged in written {
+        if let Err(err) = write_atomic(&staged.manifest.path, &staged.original) {
+            log::error!(
+                "failed to restore {}: {err:?}",
+                staged.manifest.path.display()
+            );
+        }
+    }
+}
+
+/// Replace the file at `path` with `contents`, keeping its permissions.
+///
+/// The contents are written to a temporary file in the same directory which is then renamed over
+/// `path`, so readers see either the old or the new file, never a mix. When `path` is a
+/// symlink, the file it points to is replaced and the link is kept.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> CargoResult<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let parent = target
+        .parent()
+        .with_context(|| format!("{} has no parent directory", path.display()))?;
+    let file_name = target
+        .file_name()
+        .with_context(|| format!("{} has no file name", path.display()))?;
+    let tmp_path = parent.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    let permissions = fs::metadata(&target).ok().map(|m| m.permissions());
+
+    let result = (|| -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        if let Some(permissions) = permissions {
+            fs::set_permissions(&tmp_path, permissions)?;
+        }
+        fs::rename(&tmp_path, &target)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scratch_manifest(name: &str, contents: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "cargo-edit-transaction-{}-{name}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Cargo.toml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn commit_writes_modified_manifests() {
+        let path = scratch_manifest("commit", "[package]\nversion = \"0.1.0\"\n");
+
+        let mut transaction = ManifestTransaction::new();
+        let manifest = transaction.manifest_mut(&path).unwrap();
+        manifest.set_package_version(&semver::Version::new(0, 2, 0));
+        assert!(transaction.is_modified());
+        transaction.commit(|| Ok(())).unwrap();
+
+        let actual = fs::read_to_string(&path).unwrap();
+        assert_eq!(actual, "[package]\nversion = \"0.2.0\"\n");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn failed_validation_restores_manifests() {
+        let original = "[package]\nversion = \"0.1.0\" # keep\n";
+        let path = scratch_manifest("rollback", original);
+
+        let mut transaction = ManifestTransaction::new();
+        let manifest = transaction.manifest_mut(&path).unwrap();
+        manifest.set_package_version(&semver::Version::new(0, 2, 0));
+        let err = transaction
+            .commit(|| anyhow::bail!("invalid workspace"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "rolled back manifest changes");
+
+        let actual = fs::read_to_string(&path).unwrap();
+        assert_eq!(actual, original);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_keeps_symlinks() {
+        let path = scratch_manifest("symlink", "[package]\nversion = \"0.1.0\"\n");
+        let link = path.with_file_name("Link.toml");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        write_atomic(&link, b"[package]\nversion = \"0.2.0\"\n").unwrap();
+
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        let actual = fs::read_to_string(&path).unwrap();
+        assert_eq!(actual, "[package]\nversion = \"0.2.0\"\n");
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use std::io::Write;
+use std::path::Path;
 
 pub use termcolor::{Color, ColorChoice};
 use termcolor::{ColorSpec, StandardStream, WriteColor};
@@ -79,3 +80,38 @@ pub fn shell_write_stdout(fragment: impl std::fmt::Display, spec: &ColorSpec) ->
     output.reset()?;
     Ok(())
 }
+
+/// Print a unified diff between two revisions of a file, colored like `git diff`
+pub fn shell_write_diff(path: &Path, original: &str, modified: &str) -> CargoResult<()> {
+    if original == modified {
+        return Ok(());
+    }
+
+    let path = path.display();
+    let mut header_spec = ColorSpec::new();
+    header_spec.set_bold(true);
+    shell_write_stdout(format_args!("--- {path}\n+++ {path}\n"), &header_spec)?;
+
+    let diff = similar::TextDiff::from_lines(original, modified);
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        let mut hunk_spec = ColorSpec::new();
+        hunk_spec.set_fg(Some(Color::Cyan));
+        shell_write_stdout(format_args!("{}\n", hunk.header()), &hunk_spec)?;
+
+        for change in hunk.iter_changes() {
+            let (sign, color) = match change.tag() {
+                similar::ChangeTag::Delete => ('-', Some(Color::Red)),
+                similar::ChangeTag::Insert => ('+', Some(Color::Green)),
+                similar::ChangeTag::Equal => (' ', None),
+            };
+            let mut spec = ColorSpec::new();
+            spec.set_fg(color);
+            shell_write_stdout(format_args!("{sign}{}", change.value()), &spec)?;
+            if change.missing_newline() {
+                shell_write_stdout("\n\\ No newline at end of file\n", &ColorSpec::new())?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -126,6 +126,81 @@ static VERSION_ALPHA: &str = "alpha";
 static VERSION_BETA: &str = "beta";
 static VERSION_RC: &str = "rc";
 
+/// Which pre-releases may be upgraded to
+///
+/// Stable releases are always eligible.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PrereleasePolicy {
+    /// Only stable releases
+    Never,
+    /// Pre-releases on the same channel as the current requirement, or a later one (`alpha`, then
+    /// `beta`, then `rc`)
+    #[default]
+    SameChannel,
+    /// Any pre-release
+    Any,
+}
+
+impl PrereleasePolicy {
+    /// Whether `candidate` may replace a requirement currently on the `current` pre-release
+    pub fn allows(&self, current: &semver::Prerelease, candidate: &semver::Version) -> bool {
+        if !candidate.is_prerelease() {
+            return true;
+        }
+        match self {
+            Self::Never => false,
+            Self::Any => true,
+            Self::SameChannel => {
+                if current.is_empty() {
+                    return false;
+                }
+                let current = prerelease_channel(current);
+                let candidate = prerelease_channel(&candidate.pre);
+                match (channel_rank(current), channel_rank(candidate)) {
+                    (Some(current), Some(candidate)) => current <= candidate,
+                    _ => current == candidate,
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for PrereleasePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "never" => Ok(Self::Never),
+            "same-channel" => Ok(Self::SameChannel),
+            "any" => Ok(Self::Any),
+            _ => anyhow::bail!(
+                "invalid pre-release policy `{text}`, expected `never`, `same-channel` or `any`"
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for PrereleasePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Never => "never".fmt(f),
+            Self::SameChannel => "same-channel".fmt(f),
+            Self::Any => "any".fmt(f),
+        }
+    }
+}
+
+/// The identifier naming a pre-release's channel, like `rc` for `rc.3`
+fn prerelease_channel(pre: &semver::Prerelease) -> &str {
+    pre.as_str().split('.').next().unwrap_or_default()
+}
+
+fn channel_rank(channel: &str) -> Option<usize> {
+    [VERSION_ALPHA, VERSION_BETA, VERSION_RC]
+        .iter()
+        .position(|c| *c == channel)
+}
+
 fn prerelease_id_version(version: &semver::Version) -> CargoResult<Option<(String, Option<u64>)>> {
     if !version.pre.is_empty() {
         if let Some((alpha, numeric)) = version.pre.as_str().split_once('.') {
@@ -410,4 +485,52 @@ mod test {
             assert_req_bump("2.0.0", "=1.0.0", "=2.0.0");
         }
     }
+
+    mod prerelease {
+        use super::*;
+
+        fn allows(policy: PrereleasePolicy, current: &str, candidate: &str) -> bool {
+            let current = semver::Prerelease::new(current).unwrap();
+            let candidate = semver::Version::parse(candidate).unwrap();
+            policy.allows(&current, &candidate)
+        }
+
+        #[test]
+        fn stable_always_allowed() {
+            assert!(allows(PrereleasePolicy::Never, "rc.3", "1.0.0"));
+            assert!(allows(PrereleasePolicy::SameChannel, "", "1.0.0"));
+        }
+
+        #[test]
+        fn never() {
+            assert!(!allows(PrereleasePolicy::Never, "rc.3", "1.0.0-rc.4"));
+        }
+
+        #[test]
+        fn same_channel() {
+            assert!(!allows(PrereleasePolicy::SameChannel, "", "1.0.0-rc.1"));
+            assert!(allows(PrereleasePolicy::SameChannel, "rc.3", "1.0.0-rc.4"));
+            assert!(allows(
+                PrereleasePolicy::SameChannel,
+                "beta.2",
+                "1.0.0-rc.1"
+            ));
+            assert!(!allows(
+                PrereleasePolicy::SameChannel,
+                "beta.2",
+                "1.1.0-alpha.1"
+            ));
+            assert!(allows(PrereleasePolicy::SameChannel, "dev", "1.1.0-dev.2"));
+            assert!(!allows(
+                PrereleasePolicy::SameChannel,
+                "dev",
+                "1.1.0-alpha.1"
+            ));
+        }
+
+        #[test]
+        fn any() {
+            assert!(allows(PrereleasePolicy::Any, "", "1.0.0-alpha.1"));
+        }
+    }
 }
@@ -2,6 +2,7 @@
 #![warn(clippy::needless_borrow)]
 #![warn(clippy::redundant_clone)]
 
+mod diff;
 mod downgrade_error;
 mod dry_run;
 mod ignore_dependent;
@@ -10,10 +11,12 @@ mod package_not_found_mixed;
 mod package_not_found_repeated;
 mod packages_not_found;
 mod relative_absolute_conflict;
+mod script;
 mod set_absolute_version;
 mod set_absolute_workspace_version;
 mod set_relative_version;
 mod set_relative_workspace_version;
+mod undo;
 mod upgrade_compatible_dependency;
 mod upgrade_incompatible_dependency;
 mod upgrade_workspace;
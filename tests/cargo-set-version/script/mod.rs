@@ -0,0 +1,34 @@
+use cargo_test_support::Project;
+use cargo_test_support::compare::assert_ui;
+use cargo_test_support::file;
+use cargo_test_support::prelude::*;
+
+use crate::CargoCommand;
+use crate::init_registry;
+use cargo_test_support::current_dir;
+
+#[cargo_test]
+fn case() {
+    init_registry();
+    let project = Project::from_template(current_dir!().join("in"));
+    let project_root = project.root();
+    let cwd = &project_root;
+
+    snapbox::cmd::Command::cargo_ui()
+        .arg("set-version")
+        .args(["2.0.0", "--manifest-path", "script.rs"])
+        .env("__CARGO_TEST_CHANNEL_OVERRIDE_DO_NOT_USE_THIS", "nightly")
+        .current_dir(cwd)
+        .assert()
+        .success()
+        .stdout_eq(file!["stdout.term.svg"])
+        .stderr_eq(file!["stderr.term.svg"]);
+
+    assert_ui().subset_matches(current_dir!().join("out"), &project_root);
+
+    // Everything after the frontmatter is left byte-for-byte alone
+    let code = |script: &str| script.split_once("---\n\n").unwrap().1.to_owned();
+    let before = std::fs::read_to_string(current_dir!().join("in/script.rs")).unwrap();
+    let after = std::fs::read_to_string(project_root.join("script.rs")).unwrap();
+    assert_eq!(code(&before), code(&after));
+}
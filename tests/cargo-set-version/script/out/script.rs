@@ -0,0 +1,16 @@
+#!/usr/bin/env cargo
+---cargo
+[package]
+version = "2.0.0"
+edition = "2021"
+---
+
+//! The code below must survive untouched
+const MANIFEST: &str = r#"
+[package]
+version = "0.4.3"
+"#;
+
+fn main() {
+	print!("{MANIFEST}---/n");  
+}
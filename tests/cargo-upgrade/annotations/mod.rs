@@ -0,0 +1,53 @@
+use cargo_test_support::Project;
+use cargo_test_support::compare::assert_ui;
+use cargo_test_support::file;
+use cargo_test_support::prelude::*;
+
+use crate::CargoCommand;
+use crate::init_registry;
+use cargo_test_support::current_dir;
+
+#[cargo_test]
+fn case() {
+    init_registry();
+    let (git_project, repo) = cargo_test_support::git::new_repo("forks", |project| {
+        project
+            .file(
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"my-package1\", \"my-package2\"]\n",
+            )
+            .file(
+                "my-package1/Cargo.toml",
+                &cargo_test_support::basic_manifest("my-package1", "0.1.1"),
+            )
+            .file("my-package1/src/lib.rs", "")
+            .file(
+                "my-package2/Cargo.toml",
+                &cargo_test_support::basic_manifest("my-package2", "0.4.1"),
+            )
+            .file("my-package2/src/lib.rs", "")
+    });
+    for tag in ["v0.1.1", "v0.1.5", "v20.0.0"] {
+        cargo_test_support::git::tag(&repo, tag);
+    }
+    let project = Project::from_template(current_dir!().join("in"));
+    let project_root = project.root();
+    let cwd = &project_root;
+    let manifest = std::fs::read_to_string(project_root.join("Cargo.toml")).unwrap();
+    std::fs::write(
+        project_root.join("Cargo.toml"),
+        manifest.replace("[FORKS]", git_project.url().as_str()),
+    )
+    .unwrap();
+
+    snapbox::cmd::Command::cargo_ui()
+        .arg("upgrade")
+        .args(["--incompatible", "--verbose"])
+        .current_dir(cwd)
+        .assert()
+        .success()
+        .stdout_eq(file!["stdout.term.svg"])
+        .stderr_eq(file!["stderr.term.svg"]);
+
+    assert_ui().subset_matches(current_dir!().join("out"), &project_root);
+}
@@ -0,0 +1,10 @@
+#!/usr/bin/env cargo
+---cargo
+[package]
+edition = "2021"
+
+[dependencies]
+my-package = "99999.0.0"
+---
+
+fn main() {}
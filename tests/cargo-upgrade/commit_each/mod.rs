@@ -0,0 +1,44 @@
+use cargo_test_support::Project;
+use cargo_test_support::compare::assert_ui;
+use cargo_test_support::file;
+use cargo_test_support::git;
+use cargo_test_support::prelude::*;
+use cargo_test_support::str;
+
+use crate::CargoCommand;
+use crate::init_registry;
+use cargo_test_support::current_dir;
+
+#[cargo_test]
+fn case() {
+    init_registry();
+    let project = Project::from_template(current_dir!().join("in"));
+    let project_root = project.root();
+    let cwd = &project_root;
+    let repo = git::init(&project_root);
+    git::add(&repo);
+    git::commit(&repo);
+
+    snapbox::cmd::Command::cargo_ui()
+        .arg("upgrade")
+        .args(["--incompatible", "--commit-each"])
+        .current_dir(cwd)
+        .assert()
+        .success()
+        .stdout_eq(file!["stdout.term.svg"])
+        .stderr_eq(file!["stderr.term.svg"]);
+
+    snapbox::cmd::Command::new("git")
+        .args(["log", "--format=%s"])
+        .current_dir(cwd)
+        .assert()
+        .success()
+        .stdout_eq(str![[r#"
+Upgrade my-package2 0.1.1 -> 99999.0.0 in cargo-list-test-fixture
+Upgrade my-package1 0.1.1 -> 99999.0.0 in cargo-list-test-fixture
+test
+
+"#]]);
+
+    assert_ui().subset_matches(current_dir!().join("out"), &project_root);
+}
@@ -0,0 +1,47 @@
+use cargo_test_support::Project;
+use cargo_test_support::compare::assert_ui;
+use cargo_test_support::file;
+use cargo_test_support::prelude::*;
+use cargo_test_support::registry::Package;
+
+use crate::CargoCommand;
+use cargo_test_support::current_dir;
+
+#[cargo_test]
+fn case() {
+    cargo_test_support::registry::init();
+    Package::new("log", "0.3.9").publish();
+    Package::new("log", "0.4.20").publish();
+    Package::new("base", "1.0.0").publish();
+    Package::new("base", "2.0.0").publish();
+    Package::new("old", "1.0.0").publish();
+    Package::new("old", "2.0.0").publish();
+    // Upgrading `app-a` drops both `log` 0.3 and `base` 1
+    Package::new("app-a", "1.0.0")
+        .dep("log", "0.3")
+        .dep("base", "1")
+        .publish();
+    Package::new("app-a", "2.0.0")
+        .dep("log", "0.4")
+        .dep("base", "2")
+        .publish();
+    // `old` 1 is only held through `middle`, released by upgrading `app-b`
+    Package::new("middle", "1.0.0").dep("old", "1").publish();
+    Package::new("middle", "2.0.0").dep("old", "2").publish();
+    Package::new("app-b", "1.0.0").dep("middle", "1").publish();
+    Package::new("app-b", "2.0.0").dep("middle", "2").publish();
+    let project = Project::from_template(current_dir!().join("in"));
+    let project_root = project.root();
+    let cwd = &project_root;
+
+    snapbox::cmd::Command::cargo_ui()
+        .arg("upgrade")
+        .args(["--dedupe"])
+        .current_dir(cwd)
+        .assert()
+        .success()
+        .stdout_eq(file!["stdout.term.svg"])
+        .stderr_eq(file!["stderr.term.svg"]);
+
+    assert_ui().subset_matches(current_dir!().join("out"), &project_root);
+}
@@ -2,11 +2,26 @@
 #![warn(clippy::needless_borrow)]
 #![warn(clippy::redundant_clone)]
 
+mod advisory_db;
+mod aliased_majors;
+mod aliased_majors_incompatible;
 mod alt_registry;
+mod annotations;
+mod apply_plan;
+mod apply_plan_outside;
+mod apply_plan_script;
+mod bump_rust_version;
+mod bump_rust_version_workspace;
 mod cli_rust_version;
+mod coherent;
+mod coherent_members;
+mod commit_each;
+mod dedupe;
+mod diff;
 mod dry_run;
 mod exclude_dep;
 mod exclude_renamed;
+mod explain;
 mod ignore_rust_version;
 mod implicit_prerelease;
 mod invalid_dep;
@@ -16,27 +31,45 @@ mod invalid_workspace_root_manifest;
 mod locked;
 mod locked_dry_run;
 mod lockfile;
+mod member;
 mod optional_dep;
+mod package_glob;
 mod package_rust_version;
+mod patch;
 mod pinned;
+mod plan_out;
+mod prerelease_any;
+mod prerelease_never;
+mod prerelease_same_channel;
 mod preserve_op;
 mod preserve_precision_major;
 mod preserve_precision_minor;
 mod preserve_precision_patch;
 mod preserves_inline_table;
 mod preserves_std_table;
+mod recursive_dirs;
+mod rollback_unresolvable;
+mod script;
 mod single_dep;
 mod skip_compatible;
 mod specified;
+mod staleness;
+mod summary_markdown;
+mod target_triple;
 mod to_version;
+mod undo;
+mod unused_patch;
+mod unused_patch_dry_run;
 mod upgrade_all;
 mod upgrade_everything;
 mod upgrade_renamed;
 mod upgrade_verbose;
 mod upgrade_verbose_hint;
 mod upgrade_workspace;
+mod verify;
 mod virtual_manifest;
 mod workspace_inheritance;
+mod workspace_inheritance_root_package;
 mod workspace_member_cwd;
 mod workspace_member_manifest_path;
 
@@ -217,6 +250,18 @@ fn add_op_registry_packages(alt: bool) {
         .publish();
 }
 
+fn add_coherent_registry_packages(alt: bool) {
+    for version in ["0.1.0", "0.1.5", "0.2.0"] {
+        cargo_test_support::registry::Package::new("coherent-base", version)
+            .alternative(alt)
+            .publish();
+    }
+    cargo_test_support::registry::Package::new("coherent-ext", "0.1.0")
+        .dep("coherent-base", "0.1")
+        .alternative(alt)
+        .publish();
+}
+
 pub fn cargo_exe() -> &'static std::path::Path {
     snapbox::cmd::cargo_bin!("cargo-upgrade")
 }